@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use crate::{
+    movement::resolve_movements,
+    person::{Person, SPRITE_SIZE},
+    states::GameState,
+};
+
+/// The character sheets people are drawn from, one picked for each arrival.
+pub const PERSON_SHEETS: [&str; 4] = [
+    "persons/adam.png",
+    "persons/bea.png",
+    "persons/chloe.png",
+    "persons/dev.png",
+];
+/// Frames in a row of a character sheet: two idle frames, then four walking ones.
+pub const SHEET_COLUMNS: usize = 6;
+/// One row per facing, in the order of `Facing`.
+pub const SHEET_ROWS: usize = 4;
+
+/// Time a frame stays on screen, in seconds.
+const FRAME_DURATION: f32 = 0.15;
+/// Columns played while standing around: mostly still, with a blink at the end.
+const IDLE_CYCLE: [usize; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+/// Columns played while walking.
+const WALK_CYCLE: [usize; 4] = [2, 3, 4, 5];
+/// The speed above which a person is shown walking, in pixels per second.
+const WALK_SPEED: f32 = 3.0;
+
+/// Where a person looks, which is the row of their sheet.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Facing {
+    #[default]
+    Down,
+    Up,
+    Left,
+    Right,
+}
+
+impl Facing {
+    /// The facing closest to a movement, on its main axis
+    fn from_movement(movement: Vec2) -> Facing {
+        match movement.x.abs() > movement.y.abs() {
+            true if movement.x > 0.0 => Facing::Right,
+            true => Facing::Left,
+            false if movement.y > 0.0 => Facing::Up,
+            false => Facing::Down,
+        }
+    }
+}
+
+/// Component for animated people.
+#[derive(Component, Debug)]
+pub struct Animation {
+    pub timer: Timer,
+    /// Frames played since the cycle started
+    pub frame: usize,
+    pub facing: Facing,
+    pub walking: bool,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            timer: Timer::from_seconds(FRAME_DURATION, TimerMode::Repeating),
+            // So that people don't all blink at once
+            frame: thread_rng().gen_range(0..IDLE_CYCLE.len()),
+            facing: Facing::default(),
+            walking: false,
+        }
+    }
+}
+
+impl Animation {
+    /// The index of the current frame in the character sheet
+    pub fn sheet_index(&self) -> usize {
+        let column = match self.walking {
+            true => WALK_CYCLE[self.frame % WALK_CYCLE.len()],
+            false => IDLE_CYCLE[self.frame % IDLE_CYCLE.len()],
+        };
+        self.facing as usize * SHEET_COLUMNS + column
+    }
+}
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            animate_persons
+                .after(resolve_movements)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Cut a character sheet into its frames
+pub fn person_atlas(sheet: Handle<Image>) -> TextureAtlas {
+    TextureAtlas::from_grid(
+        sheet,
+        SPRITE_SIZE.truncate(),
+        SHEET_COLUMNS,
+        SHEET_ROWS,
+        None,
+        None,
+    )
+}
+
+/// Face where people went this frame, walk if they went fast enough, and play the cycle
+fn animate_persons(
+    mut persons_query: Query<(&Person, &mut Animation, &mut TextureAtlasSprite)>,
+    time: Res<Time>,
+) {
+    if time.delta_seconds() == 0.0 {
+        return;
+    }
+    for (person, mut animation, mut sprite) in &mut persons_query {
+        // The movement vector now holds how far the person went this frame
+        let walking = person.movement_vector.length() / time.delta_seconds() > WALK_SPEED;
+        if walking {
+            animation.facing = Facing::from_movement(person.movement_vector);
+        }
+        if walking != animation.walking {
+            animation.walking = walking;
+            animation.frame = 0;
+            animation.timer.reset();
+        }
+        if animation.timer.tick(time.delta()).just_finished() {
+            animation.frame += animation.timer.times_finished_this_tick() as usize;
+        }
+        sprite.index = animation.sheet_index();
+    }
+}
@@ -1,9 +1,15 @@
 use bevy::prelude::*;
 
+use crate::{
+    animation::{person_atlas, PERSON_SHEETS},
+    emote::emote_atlas,
+};
+
 #[derive(Debug, Default, Resource)]
 pub struct AssetHandles {
     pub street: Handle<Image>,
-    pub person: Handle<Image>,
+    /// One per character sheet
+    pub persons: Vec<Handle<TextureAtlas>>,
     pub selector: Handle<Image>,
     pub house: Handle<Image>,
     pub forum: Handle<Image>,
@@ -14,6 +20,7 @@ pub struct AssetHandles {
     pub creative: Handle<Image>,
     pub tree: Handle<Image>,
     pub lamp: Handle<Image>,
+    pub emotes: Handle<TextureAtlas>,
 }
 
 pub struct AssetLoaderPlugin;
@@ -26,8 +33,15 @@ impl Plugin for AssetLoaderPlugin {
 }
 
 /// Load assets once, provide handles for the rest of the game
-fn load_assets(mut asset_handles: ResMut<AssetHandles>, asset_server: Res<AssetServer>) {
-    asset_handles.person = asset_server.load("Adam_idle_front.png");
+fn load_assets(
+    mut asset_handles: ResMut<AssetHandles>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    asset_handles.persons = PERSON_SHEETS
+        .iter()
+        .map(|path| texture_atlases.add(person_atlas(asset_server.load(*path))))
+        .collect();
     asset_handles.street = asset_server.load("street.png");
     asset_handles.selector = asset_server.load("selector.png");
     asset_handles.house = asset_server.load("house.png");
@@ -39,4 +53,5 @@ fn load_assets(mut asset_handles: ResMut<AssetHandles>, asset_server: Res<AssetS
     asset_handles.creative = asset_server.load("creative_mart.png");
     asset_handles.tree = asset_server.load("tree.png");
     asset_handles.lamp = asset_server.load("lamp.png");
+    asset_handles.emotes = texture_atlases.add(emote_atlas(asset_server.load("emotes.png")));
 }
@@ -0,0 +1,386 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    asset::LoadState,
+    audio::AudioPlugin,
+    log::LogPlugin,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    time::TimeUpdateStrategy,
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+
+use crate::{
+    asset_loader::AssetHandles,
+    blueprint::start_pasting,
+    building::{
+        footprint, spawn_building, trigger_spawn, BuildingCooldowns, BuildingType,
+        UnlockedBuildings,
+    },
+    economy::Treasury,
+    ground::{Ground, TILE_SIZE},
+    mapgen::{MapSettings, MapSource},
+    overlay::lacks_need,
+    person::Person,
+    score::Score,
+    states::GameState,
+    stats::NEEDS,
+    telemetry::{export_telemetry, Telemetry},
+};
+
+/// How long a simulated frame lasts, in seconds.
+const BALANCE_TICK: f32 = 1.0 / 30.0;
+/// Time between two decisions of the scripted player, in seconds.
+const POLICY_PERIOD: f32 = 1.0;
+/// How far from the wanted spot a building may be placed, in tiles.
+const PLACEMENT_REACH: i32 = 20;
+/// How long to wait for the images to load before playing anyway.
+const LOADING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the scripted player builds the city.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    /// Never builds anything, as a baseline
+    Idle,
+    /// Builds what satisfies the need the most people lack, at the
+    /// centroid of these people, whenever it is in stock and affordable
+    MostLacking,
+}
+
+impl Policy {
+    fn id(self) -> &'static str {
+        match self {
+            Policy::Idle => "idle",
+            Policy::MostLacking => "most-lacking",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        [Policy::Idle, Policy::MostLacking]
+            .into_iter()
+            .find(|policy| policy.id() == id)
+    }
+}
+
+/// What a balance run plays.
+#[derive(Debug, Clone)]
+pub struct BalanceSettings {
+    pub policy: Policy,
+    pub seeds: u64,
+    /// Simulated time per seed
+    pub seconds: f32,
+    /// Export the telemetry of every seed
+    pub telemetry: bool,
+}
+
+impl BalanceSettings {
+    /// Read `--balance [--policy <id>] [--seeds <count>] [--seconds <s>] [--telemetry]`
+    /// from the command line, or `None` without `--balance`
+    pub fn from_args(args: &[String]) -> Option<Result<Self, String>> {
+        if !args.iter().any(|arg| arg == "--balance") {
+            return None;
+        }
+        let mut settings = BalanceSettings {
+            policy: Policy::MostLacking,
+            seeds: 10,
+            seconds: 600.0,
+            telemetry: false,
+        };
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value after {}", arg));
+            match arg.as_str() {
+                "--balance" => {}
+                "--telemetry" => settings.telemetry = true,
+                "--policy" => match value().map(|id| Policy::from_id(id)) {
+                    Ok(Some(policy)) => settings.policy = policy,
+                    _ => return Some(Err("Policies are idle and most-lacking".to_string())),
+                },
+                "--seeds" => match value().map(|count| count.parse()) {
+                    Ok(Ok(count)) => settings.seeds = count,
+                    _ => return Some(Err("--seeds takes a number of seeds".to_string())),
+                },
+                "--seconds" => match value().map(|seconds| seconds.parse()) {
+                    Ok(Ok(seconds)) => settings.seconds = seconds,
+                    _ => return Some(Err("--seconds takes a duration".to_string())),
+                },
+                _ => return Some(Err(format!("Unknown balance option {}", arg))),
+            }
+        }
+        Some(Ok(settings))
+    }
+}
+
+/// How the city ended up after a run.
+#[derive(Debug, Clone)]
+struct Outcome {
+    seed: u64,
+    score: f32,
+    population: usize,
+    buildings: usize,
+}
+
+#[derive(Resource, Debug)]
+struct PolicyTimer(Timer);
+
+/// Play every seed with the scripted player, without a window, and report the scores
+pub fn run_balance(settings: &BalanceSettings, add_game: fn(&mut App)) {
+    println!(
+        "Policy {}, {}s per seed, {} seeds",
+        settings.policy.id(),
+        settings.seconds,
+        settings.seeds
+    );
+    let outcomes: Vec<Outcome> = (1..=settings.seeds)
+        .map(|seed| {
+            let outcome = run_seed(settings, seed, add_game);
+            println!(
+                "seed {}: score {:.1}, population {}, buildings {}",
+                outcome.seed, outcome.score, outcome.population, outcome.buildings
+            );
+            outcome
+        })
+        .collect();
+
+    let scores: Vec<f32> = outcomes.iter().map(|outcome| outcome.score).collect();
+    println!("score: {}", distribution(&scores));
+    let populations: Vec<f32> = outcomes
+        .iter()
+        .map(|outcome| outcome.population as f32)
+        .collect();
+    println!("population: {}", distribution(&populations));
+}
+
+/// Play one seed from the start, with time going as fast as the computer can
+fn run_seed(settings: &BalanceSettings, seed: u64, add_game: fn(&mut App)) -> Outcome {
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            })
+            // Nothing is drawn, so no GPU is needed
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }
+                .into(),
+            })
+            .disable::<WinitPlugin>()
+            .disable::<AudioPlugin>()
+            .disable::<LogPlugin>(),
+    )
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+        BALANCE_TICK,
+    )));
+    add_game(&mut app);
+    app.insert_resource(MapSettings {
+        source: MapSource::Generated,
+        seed,
+        starter_buildings: true,
+    })
+    .insert_resource(settings.policy)
+    .insert_resource(PolicyTimer(Timer::from_seconds(
+        POLICY_PERIOD,
+        TimerMode::Repeating,
+    )))
+    .insert_resource(Telemetry {
+        recording: settings.telemetry,
+        ..default()
+    })
+    .add_systems(
+        Update,
+        play_policy
+            .after(start_pasting)
+            .before(trigger_spawn)
+            .run_if(in_state(GameState::Playing)),
+    );
+    app.finish();
+    app.cleanup();
+
+    // Sprites get their bounds once their image is loaded, which moves them
+    // around in the world and so changes the order the simulation sees them in
+    let loading = Instant::now();
+    while !images_settled(&app.world) {
+        if loading.elapsed() > LOADING_TIMEOUT {
+            eprintln!(
+                "Images are still loading, seed {} may not replay the same",
+                seed
+            );
+            break;
+        }
+        app.update();
+    }
+    app.world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Playing);
+
+    let ticks = (settings.seconds / BALANCE_TICK).round() as usize;
+    for _ in 0..ticks {
+        app.update();
+    }
+
+    if settings.telemetry {
+        let name = format!("balance_{}_{}", settings.policy.id(), seed);
+        if let Err(error) = export_telemetry(app.world.resource::<Telemetry>(), &name) {
+            eprintln!("Could not export the telemetry of seed {}: {}", seed, error);
+        }
+    }
+    let population = app.world.query::<&Person>().iter(&app.world).count();
+    let buildings = app.world.query::<&BuildingType>().iter(&app.world).count();
+    Outcome {
+        seed,
+        score: app.world.resource::<Score>().0,
+        population,
+        buildings,
+    }
+}
+
+/// Whether every image the game draws has finished loading, or failed to
+fn images_settled(world: &World) -> bool {
+    let handles = world.resource::<AssetHandles>();
+    let atlases = world.resource::<Assets<TextureAtlas>>();
+    let asset_server = world.resource::<AssetServer>();
+    let sheets = handles
+        .persons
+        .iter()
+        .chain([&handles.emotes])
+        .map(|atlas| atlases.get(atlas).map(|atlas| atlas.texture.id()));
+    [
+        &handles.street,
+        &handles.selector,
+        &handles.house,
+        &handles.forum,
+        &handles.cinema,
+        &handles.hospital,
+        &handles.pool,
+        &handles.restaurant,
+        &handles.creative,
+        &handles.tree,
+        &handles.lamp,
+    ]
+    .into_iter()
+    .map(|image| Some(image.id()))
+    .chain(sheets)
+    .all(|image| {
+        image.is_some_and(|id| {
+            matches!(
+                asset_server.get_load_state(id),
+                Some(LoadState::Loaded | LoadState::Failed)
+            )
+        })
+    })
+}
+
+/// The mean, spread and quartiles of some values
+fn distribution(values: &[f32]) -> String {
+    if values.is_empty() {
+        return "no values".to_string();
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let count = sorted.len() as f32;
+    let mean = sorted.iter().sum::<f32>() / count;
+    let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / count;
+    let quantile = |q: f32| sorted[((count - 1.0) * q).round() as usize];
+    format!(
+        "mean {:.1}, std dev {:.1}, min {:.1}, p25 {:.1}, median {:.1}, p75 {:.1}, max {:.1}",
+        mean,
+        variance.sqrt(),
+        quantile(0.0),
+        quantile(0.25),
+        quantile(0.5),
+        quantile(0.75),
+        quantile(1.0)
+    )
+}
+
+/// Build for the scripted player, like it would by pressing the building's key
+/// and dragging it in place
+#[allow(clippy::too_many_arguments)]
+fn play_policy(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    policy: Res<Policy>,
+    mut policy_timer: ResMut<PolicyTimer>,
+    persons_query: Query<(&Person, &Transform)>,
+    buildings_query: Query<(&BuildingType, &Transform)>,
+    mut cooldowns: ResMut<BuildingCooldowns>,
+    unlocked: Res<UnlockedBuildings>,
+    mut treasury: ResMut<Treasury>,
+    ground: Res<Ground>,
+    time: Res<Time>,
+) {
+    if !policy_timer.0.tick(time.delta()).just_finished() || *policy == Policy::Idle {
+        return;
+    }
+
+    // The need the most people lack, among those that can be built for right now
+    let available = |b_type: BuildingType| {
+        unlocked.0.contains(&b_type)
+            && treasury.can_afford(b_type)
+            && cooldowns.0.get(&b_type).is_some_and(|c| c.stock > 0)
+    };
+    let Some((b_type, lacking)) = NEEDS
+        .iter()
+        .filter(|(b_type, _)| available(*b_type))
+        .map(|(b_type, _)| {
+            let lacking: Vec<Vec2> = persons_query
+                .iter()
+                .filter(|(person, _)| lacks_need(person, *b_type))
+                .map(|(_, transform)| transform.translation.truncate())
+                .collect();
+            (*b_type, lacking)
+        })
+        .filter(|(_, lacking)| !lacking.is_empty())
+        .max_by_key(|(_, lacking)| lacking.len())
+    else {
+        return;
+    };
+
+    let centroid = lacking.iter().sum::<Vec2>() / lacking.len() as f32;
+    let Some(position) = free_spot(b_type, centroid, &ground, &buildings_query) else {
+        return;
+    };
+    if let Some(paid) = treasury.buy(b_type) {
+        let entity = spawn_building(b_type, position, &mut commands, &asset_handles);
+        commands.entity(entity).insert(paid);
+        if let Some(cooldown) = cooldowns.0.get_mut(&b_type) {
+            cooldown.stock -= 1;
+        }
+    }
+}
+
+/// The closest spot to the wanted one where a building of the given
+/// type can stand without overlapping another one
+fn free_spot(
+    b_type: BuildingType,
+    wanted: Vec2,
+    ground: &Ground,
+    buildings_query: &Query<(&BuildingType, &Transform)>,
+) -> Option<Vec2> {
+    let size = footprint(b_type, &Transform::default());
+    let overlaps = |position: Vec2| {
+        let rect = Rect::from_center_size(position, size);
+        buildings_query.iter().any(|(other, transform)| {
+            let other_rect = Rect::from_center_size(
+                transform.translation.truncate(),
+                footprint(*other, transform),
+            );
+            !rect.intersect(other_rect).is_empty()
+        })
+    };
+
+    let mut candidates: Vec<Vec2> = (-PLACEMENT_REACH..=PLACEMENT_REACH)
+        .flat_map(|x| (-PLACEMENT_REACH..=PLACEMENT_REACH).map(move |y| IVec2::new(x, y)))
+        .map(|offset| wanted + offset.as_vec2() * TILE_SIZE.truncate())
+        .collect();
+    candidates.sort_by(|a, b| a.distance(wanted).total_cmp(&b.distance(wanted)));
+    candidates
+        .into_iter()
+        .find(|position| ground.can_build(b_type, *position, size) && !overlaps(*position))
+}
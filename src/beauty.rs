@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+use crate::building::BuildingType;
+
+/// The distance below which decorations count toward a person's surroundings.
+pub const BEAUTY_RADIUS: f32 = 64.0;
+/// The most satisfaction pleasant surroundings can give.
+const MAX_BEAUTY: f32 = 6.0;
+/// The weight of decorations at which surroundings give about two thirds of their best.
+const BEAUTY_SATURATION: f32 = 3.0;
+/// Decorations around a person past which more of them clutter the place.
+const CROWDING_START: usize = 8;
+/// The satisfaction lost to each decoration past the crowding start.
+const CROWDING_PENALTY: f32 = 1.0;
+/// The most satisfaction a cluttered place can take away.
+const MAX_CROWDING: f32 = 5.0;
+
+/// How much a decoration embellishes its surroundings, if at all
+pub fn decoration_weight(b_type: BuildingType) -> f32 {
+    match b_type {
+        BuildingType::Tree => 1.0,
+        BuildingType::Lamp => 0.5,
+        _ => 0.0,
+    }
+}
+
+/// The satisfaction given (or taken away, when crowded) by the decorations
+/// around a position. Nearer decorations weigh more, and each one adds less
+/// than the previous
+pub fn beauty(position: Vec2, decorations: &[(BuildingType, Vec2)]) -> f32 {
+    let nearby: Vec<(BuildingType, f32)> = decorations
+        .iter()
+        .map(|(b_type, decoration)| (*b_type, decoration.distance(position)))
+        .filter(|(b_type, distance)| *distance < BEAUTY_RADIUS && decoration_weight(*b_type) > 0.0)
+        .collect();
+
+    let weight: f32 = nearby
+        .iter()
+        .map(|(b_type, distance)| decoration_weight(*b_type) * (1.0 - distance / BEAUTY_RADIUS))
+        .sum();
+    let embellishment = MAX_BEAUTY * (1.0 - (-weight / BEAUTY_SATURATION).exp());
+
+    let crowding = nearby.len().saturating_sub(CROWDING_START) as f32 * CROWDING_PENALTY;
+    embellishment - crowding.min(MAX_CROWDING)
+}
@@ -0,0 +1,598 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{input::InputSystem, prelude::*, utils::HashMap, window::ReceivedCharacter};
+
+use crate::{
+    asset_loader::AssetHandles,
+    building::{
+        building_texture, footprint, spawn_building, BuildingCooldowns, BuildingType,
+        UnlockedBuildings,
+    },
+    camera::CursorPosition,
+    debug::TEXT_SIZE,
+    depth::STANDING_LEVEL,
+    economy::{placement_cost, Paid, Treasury},
+    ground::Ground,
+    history::{Action, ActionDone},
+    minimap::click_minimap,
+    notification::{Category, Notification},
+    replay::replay_inputs,
+    selection::{delete_selection, Selected},
+    states::GameState,
+};
+
+/// The blueprints shipped with the game.
+const BUNDLED_BLUEPRINTS: [&str; 1] = [include_str!("../assets/blueprints/block.blueprint")];
+
+/// Where the player's blueprints are saved, one file each.
+#[cfg(not(target_arch = "wasm32"))]
+const BLUEPRINTS_DIR: &str = "blueprints";
+
+const MAX_NAME_LENGTH: usize = 30;
+
+// for z-ordering
+const GHOST_LEVEL: f32 = 10.0;
+
+/// A building of a blueprint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlueprintPart {
+    pub b_type: BuildingType,
+    /// From the blueprint's center, in world units
+    pub offset: Vec2,
+    pub quarter_turns: u32,
+}
+
+impl BlueprintPart {
+    /// Where the part goes when its blueprint is centered on `center`
+    fn transform(&self, center: Vec2, level: f32) -> Transform {
+        Transform::from_translation((center + self.offset).extend(level))
+            .with_rotation(Quat::from_rotation_z(self.quarter_turns as f32 * FRAC_PI_2))
+    }
+}
+
+/// A layout of buildings that can be pasted again and again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blueprint {
+    pub name: String,
+    pub parts: Vec<BlueprintPart>,
+}
+
+impl Blueprint {
+    /// The layout of the given buildings, centered on their average position
+    pub fn from_buildings<'a>(
+        name: &str,
+        buildings: impl Iterator<Item = (&'a BuildingType, &'a Transform)>,
+    ) -> Self {
+        let buildings: Vec<_> = buildings.collect();
+        let center = buildings
+            .iter()
+            .map(|(_, transform)| transform.translation.truncate())
+            .sum::<Vec2>()
+            / buildings.len().max(1) as f32;
+
+        let parts = buildings
+            .iter()
+            .map(|(b_type, transform)| {
+                let angle = transform.rotation.to_euler(EulerRot::XYZ).2;
+                BlueprintPart {
+                    b_type: **b_type,
+                    offset: transform.translation.truncate() - center,
+                    quarter_turns: (angle / FRAC_PI_2).round().rem_euclid(4.0) as u32,
+                }
+            })
+            .collect();
+        Blueprint {
+            name: name.to_string(),
+            parts,
+        }
+    }
+
+    /// Read a blueprint file. Each line starts with a keyword:
+    ///
+    /// - `name <text>`
+    /// - `building <type> <x> <y> [quarter turns]`, offset from the blueprint's center
+    ///
+    /// `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut blueprint = Blueprint {
+            name: String::new(),
+            parts: vec![],
+        };
+
+        for (line_nb, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = || format!("Invalid blueprint line {}: {}", line_nb + 1, line);
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let words: Vec<&str> = rest.split_whitespace().collect();
+
+            match (keyword, &words[..]) {
+                ("name", _) => blueprint.name = rest.trim().to_string(),
+                ("building", [b_type, x_pos, y_pos, turns @ ..]) => {
+                    let quarter_turns = match turns {
+                        [] => 0,
+                        [turns] => turns.parse().map_err(|_| error())?,
+                        _ => return Err(error()),
+                    };
+                    blueprint.parts.push(BlueprintPart {
+                        b_type: BuildingType::from_id(b_type).ok_or_else(error)?,
+                        offset: Vec2::new(
+                            x_pos.parse().map_err(|_| error())?,
+                            y_pos.parse().map_err(|_| error())?,
+                        ),
+                        quarter_turns,
+                    });
+                }
+                _ => return Err(error()),
+            }
+        }
+
+        if blueprint.name.is_empty() {
+            return Err("Blueprint without a name".to_string());
+        }
+        Ok(blueprint)
+    }
+
+    /// Write the blueprint the way `parse` reads it
+    pub fn to_text(&self) -> String {
+        let mut text = format!("name {}\n", self.name);
+        for part in &self.parts {
+            text.push_str(&format!(
+                "building {} {} {} {}\n",
+                part.b_type.id(),
+                part.offset.x.round(),
+                part.offset.y.round(),
+                part.quarter_turns
+            ));
+        }
+        text
+    }
+
+    /// How many buildings of each type it takes
+    fn counts(&self) -> HashMap<BuildingType, u32> {
+        let mut counts = HashMap::new();
+        for part in &self.parts {
+            *counts.entry(part.b_type).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Every blueprint the player can paste, bundled ones first.
+#[derive(Resource, Debug)]
+pub struct Blueprints(pub Vec<Blueprint>);
+
+impl Default for Blueprints {
+    fn default() -> Self {
+        let mut blueprints: Vec<Blueprint> = BUNDLED_BLUEPRINTS
+            .iter()
+            .map(|text| Blueprint::parse(text).expect("Invalid bundled blueprint"))
+            .collect();
+        blueprints.extend(load_saved_blueprints());
+        Blueprints(blueprints)
+    }
+}
+
+/// The last copied buildings.
+#[derive(Resource, Debug, Default)]
+pub struct Clipboard(pub Option<Blueprint>);
+
+/// The blueprint being pasted, shown as a ghost under the cursor.
+#[derive(Resource, Debug, Default)]
+pub struct Pasting(pub Option<Blueprint>);
+
+/// Which blueprint Ctrl+B picks next, in the list of blueprints.
+#[derive(Resource, Debug, Default)]
+pub struct NextBlueprint(usize);
+
+/// The name being typed for a new blueprint, and that blueprint.
+#[derive(Resource, Debug, Default)]
+pub struct NamePrompt(Option<(String, Blueprint)>);
+
+/// A see-through building following the cursor, for the part of the pasted blueprint.
+#[derive(Component, Debug)]
+struct Ghost(usize);
+
+#[derive(Component, Debug)]
+struct PromptText;
+
+pub struct BlueprintPlugin;
+
+impl Plugin for BlueprintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Blueprints>()
+            .init_resource::<Clipboard>()
+            .init_resource::<Pasting>()
+            .init_resource::<NextBlueprint>()
+            .init_resource::<NamePrompt>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (reset_blueprints, spawn_prompt_text),
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    type_blueprint_name.after(InputSystem).after(replay_inputs),
+                    place_ghost
+                        .after(InputSystem)
+                        .after(click_minimap)
+                        .after(replay_inputs),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        copy_selection,
+                        save_selection,
+                        start_pasting,
+                        show_ghost,
+                        follow_cursor,
+                    )
+                        .chain()
+                        .after(delete_selection),
+                    update_prompt_text,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_blueprints);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_saved_blueprints() -> Vec<Blueprint> {
+    let Ok(entries) = std::fs::read_dir(BLUEPRINTS_DIR) else {
+        return vec![];
+    };
+    let mut blueprints: Vec<Blueprint> = entries
+        .filter_map(|entry| std::fs::read_to_string(entry.ok()?.path()).ok())
+        .filter_map(|text| match Blueprint::parse(&text) {
+            Ok(blueprint) => Some(blueprint),
+            Err(error) => {
+                warn!("Skipping saved blueprint: {}", error);
+                None
+            }
+        })
+        .collect();
+    blueprints.sort_by(|a, b| a.name.cmp(&b.name));
+    blueprints
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_saved_blueprints() -> Vec<Blueprint> {
+    vec![]
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_blueprint(blueprint: &Blueprint) {
+    let file_name: String = blueprint
+        .name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect();
+    let path = std::path::Path::new(BLUEPRINTS_DIR).join(format!("{}.blueprint", file_name));
+    let saved = std::fs::create_dir_all(BLUEPRINTS_DIR)
+        .and_then(|_| std::fs::write(&path, blueprint.to_text()));
+    if let Err(error) = saved {
+        warn!("Could not save blueprint to {:?}: {}", path, error);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_blueprint(_blueprint: &Blueprint) {}
+
+fn reset_blueprints(
+    mut clipboard: ResMut<Clipboard>,
+    mut pasting: ResMut<Pasting>,
+    mut next_blueprint: ResMut<NextBlueprint>,
+    mut prompt: ResMut<NamePrompt>,
+) {
+    clipboard.0 = None;
+    pasting.0 = None;
+    next_blueprint.0 = 0;
+    prompt.0 = None;
+}
+
+/// Ctrl+C copies the selected buildings, keeping their layout
+fn copy_selection(
+    keys: Res<Input<KeyCode>>,
+    selected_query: Query<(&BuildingType, &Transform), With<Selected>>,
+    mut clipboard: ResMut<Clipboard>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keys.just_pressed(KeyCode::C) || selected_query.is_empty() {
+        return;
+    }
+    let blueprint = Blueprint::from_buildings("Clipboard", selected_query.iter());
+    notifications.send(Notification::new(
+        Category::Building,
+        format!("Copied {} buildings", blueprint.parts.len()),
+    ));
+    clipboard.0 = Some(blueprint);
+}
+
+/// Ctrl+S asks for a name to save the selected buildings as a blueprint
+fn save_selection(
+    keys: Res<Input<KeyCode>>,
+    selected_query: Query<(&BuildingType, &Transform), With<Selected>>,
+    mut prompt: ResMut<NamePrompt>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keys.just_pressed(KeyCode::S) {
+        return;
+    }
+    if selected_query.is_empty() {
+        notifications.send(Notification::new(
+            Category::Building,
+            "Select buildings to save them as a blueprint",
+        ));
+        return;
+    }
+    let blueprint = Blueprint::from_buildings("", selected_query.iter());
+    prompt.0 = Some((String::new(), blueprint));
+}
+
+/// While a blueprint is being named, the keyboard types its name and
+/// nothing else: Enter saves it, Escape gives up
+pub fn type_blueprint_name(
+    mut prompt: ResMut<NamePrompt>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut blueprints: ResMut<Blueprints>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let Some((name, blueprint)) = &mut prompt.0 else {
+        characters.clear();
+        return;
+    };
+    for received in characters.read() {
+        let typeable = received.char.is_alphanumeric() || " -_'".contains(received.char);
+        if typeable && name.chars().count() < MAX_NAME_LENGTH {
+            name.push(received.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        name.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Return) && !name.trim().is_empty() {
+        blueprint.name = name.trim().to_string();
+        save_blueprint(blueprint);
+        notifications.send(Notification::new(
+            Category::Building,
+            format!("Saved blueprint {}", blueprint.name),
+        ));
+        let blueprint = blueprint.clone();
+        match blueprints.0.iter_mut().find(|b| b.name == blueprint.name) {
+            Some(existing) => *existing = blueprint,
+            None => blueprints.0.push(blueprint),
+        }
+        prompt.0 = None;
+    } else if keys.just_pressed(KeyCode::Escape) {
+        prompt.0 = None;
+    }
+    // Keep the keys typed from spawning buildings or leaving the game
+    keys.reset_all();
+}
+
+/// Ctrl+V pastes the copied buildings, Ctrl+B goes through saved blueprints
+pub fn start_pasting(
+    keys: Res<Input<KeyCode>>,
+    clipboard: Res<Clipboard>,
+    blueprints: Res<Blueprints>,
+    mut pasting: ResMut<Pasting>,
+    mut next_blueprint: ResMut<NextBlueprint>,
+    mut notifications: EventWriter<Notification>,
+) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::V) && clipboard.0.is_some() {
+        pasting.0 = clipboard.0.clone();
+    } else if keys.just_pressed(KeyCode::B) && !blueprints.0.is_empty() {
+        let blueprint = &blueprints.0[next_blueprint.0 % blueprints.0.len()];
+        next_blueprint.0 += 1;
+        notifications.send(Notification::new(
+            Category::Building,
+            format!("Blueprint: {} (right click to cancel)", blueprint.name),
+        ));
+        pasting.0 = Some(blueprint.clone());
+    }
+}
+
+/// Show a ghost of the blueprint being pasted
+fn show_ghost(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    pasting: Res<Pasting>,
+    ghosts_query: Query<Entity, With<Ghost>>,
+) {
+    if !pasting.is_changed() {
+        return;
+    }
+    for entity in &ghosts_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(blueprint) = &pasting.0 else {
+        return;
+    };
+    for (i, part) in blueprint.parts.iter().enumerate() {
+        commands.spawn((
+            SpriteBundle {
+                texture: building_texture(part.b_type, &asset_handles),
+                transform: part.transform(Vec2::ZERO, GHOST_LEVEL),
+                ..default()
+            },
+            Ghost(i),
+        ));
+    }
+}
+
+/// Keep the ghost under the cursor, tinted red where it can't be built
+fn follow_cursor(
+    pasting: Res<Pasting>,
+    cursor_pos: Res<CursorPosition>,
+    ground: Res<Ground>,
+    mut ghosts_query: Query<(&Ghost, &mut Transform, &mut Sprite)>,
+) {
+    let Some(blueprint) = &pasting.0 else {
+        return;
+    };
+    for (ghost, mut transform, mut sprite) in &mut ghosts_query {
+        let Some(part) = blueprint.parts.get(ghost.0) else {
+            continue;
+        };
+        *transform = part.transform(cursor_pos.0, GHOST_LEVEL);
+        let position = transform.translation.truncate();
+        sprite.color =
+            match ground.can_build(part.b_type, position, footprint(part.b_type, &transform)) {
+                true => Color::rgba(1.0, 1.0, 1.0, 0.5),
+                false => Color::rgba(1.0, 0.4, 0.4, 0.5),
+            };
+    }
+}
+
+/// A left click pastes the ghost, a right click puts it away.
+/// Both are kept from reaching the buildings underneath
+#[allow(clippy::too_many_arguments)]
+pub fn place_ghost(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    cursor_pos: Res<CursorPosition>,
+    mut pasting: ResMut<Pasting>,
+    selected_query: Query<Entity, With<Selected>>,
+    mut cooldowns: ResMut<BuildingCooldowns>,
+    unlocked: Res<UnlockedBuildings>,
+    mut treasury: ResMut<Treasury>,
+    ground: Res<Ground>,
+    mut notifications: EventWriter<Notification>,
+    mut actions: EventWriter<ActionDone>,
+) {
+    let Some(blueprint) = &pasting.0 else {
+        return;
+    };
+    if buttons.just_pressed(MouseButton::Right) {
+        buttons.clear_just_pressed(MouseButton::Right);
+        pasting.0 = None;
+        return;
+    }
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    buttons.clear_just_pressed(MouseButton::Left);
+
+    let counts = blueprint.counts();
+    let cost: f32 = blueprint
+        .parts
+        .iter()
+        .map(|part| placement_cost(part.b_type))
+        .sum();
+    let missing_stock = counts.iter().find(|(b_type, count)| {
+        cooldowns
+            .0
+            .get(*b_type)
+            .is_some_and(|cooldown| cooldown.stock < **count)
+    });
+    let refusal = if let Some(b_type) = counts.keys().find(|b_type| !unlocked.0.contains(*b_type)) {
+        Some(format!("{} is locked", b_type.name()))
+    } else if let Some((b_type, count)) = missing_stock {
+        Some(format!("Needs {} {} in stock", count, b_type.name()))
+    } else if treasury.balance < cost {
+        Some(format!("Not enough funds to paste (${:.0})", cost))
+    } else if !blueprint.parts.iter().all(|part| {
+        let transform = part.transform(cursor_pos.0, 0.0);
+        ground.can_build(
+            part.b_type,
+            transform.translation.truncate(),
+            footprint(part.b_type, &transform),
+        )
+    }) {
+        Some("No room to paste here".to_string())
+    } else {
+        None
+    };
+    if let Some(message) = refusal {
+        notifications.send(Notification::new(Category::Building, message));
+        return;
+    }
+
+    treasury.balance -= cost;
+    for (b_type, count) in &counts {
+        if let Some(cooldown) = cooldowns.0.get_mut(b_type) {
+            cooldown.stock -= count;
+        }
+    }
+    // The pasted buildings become the selection, to be moved together
+    for entity in &selected_query {
+        commands.entity(entity).remove::<Selected>();
+    }
+    for part in &blueprint.parts {
+        let transform = part.transform(cursor_pos.0, STANDING_LEVEL);
+        let position = transform.translation.truncate();
+        let entity = spawn_building(part.b_type, position, &mut commands, &asset_handles);
+        commands
+            .entity(entity)
+            .insert((transform, Selected, Paid(placement_cost(part.b_type))));
+        actions.send(ActionDone(Action::Placed {
+            entity,
+            b_type: part.b_type,
+            transform,
+        }));
+    }
+    pasting.0 = None;
+}
+
+fn spawn_prompt_text(mut commands: Commands) {
+    let mut prompt = TextBundle::from_section(
+        "",
+        TextStyle {
+            font_size: TEXT_SIZE,
+            color: Color::GOLD,
+            ..default()
+        },
+    )
+    .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.6))
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        // Middle of the window
+        align_self: AlignSelf::Center,
+        justify_self: JustifySelf::Center,
+        ..default()
+    });
+    prompt.visibility = Visibility::Hidden;
+
+    commands.spawn((prompt, PromptText));
+}
+
+fn update_prompt_text(
+    prompt: Res<NamePrompt>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<PromptText>>,
+) {
+    let (mut text, mut visibility) = text_query.single_mut();
+    match &prompt.0 {
+        Some((name, _)) => {
+            text.sections[0].value = format!("Blueprint name: {}_  (Enter to save)", name);
+            *visibility = Visibility::Visible;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cleanup_blueprints(
+    mut commands: Commands,
+    entities_query: Query<Entity, Or<(With<Ghost>, With<PromptText>)>>,
+) {
+    for entity in &entities_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
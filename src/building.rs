@@ -1,18 +1,26 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
 
 use crate::{
     asset_loader::AssetHandles,
+    blueprint::start_pasting,
     debug::TEXT_SIZE,
-    drag::{Draggable, Interactable},
+    depth::{YSorted, STANDING_LEVEL},
+    drag::{dragging_system, Draggable, Interactable},
+    economy::{placement_cost, Paid, Treasury},
+    ground::Ground,
+    history::{Action, ActionDone},
+    notification::{Category, Notification},
+    palette::Palette,
     states::GameState,
+    unlock::{unlock_condition, TechTree},
 };
 
 /// The minimum distance between two buildings.
 const MIN_DISTANCE: f32 = 30.0;
 
-// for z-ordering
-const BUILDING_LEVEL: f32 = 1.0;
-
 // Buildings sprite dimensions
 const HOUSE_SIZE: Vec2 = Vec2::new(48.0, 48.0);
 const FORUM_SIZE: Vec2 = Vec2::new(80.0, 96.0);
@@ -24,45 +32,68 @@ const CREATIVE_SIZE: Vec2 = Vec2::new(68.0, 42.0);
 const TREE_SIZE: Vec2 = Vec2::new(32.0, 48.0);
 const LAMP_SIZE: Vec2 = Vec2::new(10.0, 46.0);
 
-/// If a building is available to be spawned.
-#[derive(Resource, Default, Debug)]
-struct BuildingAvailable {
-    house: bool,
-    forum: bool,
-    cinema: bool,
-    hospital: bool,
-    pool: bool,
-    restaurant: bool,
-    creative: bool,
+/// The keys spawning each non-decorative building, in toolbar order.
+const TOOLBAR: [(KeyCode, BuildingType); 7] = [
+    (KeyCode::H, BuildingType::House),
+    (KeyCode::R, BuildingType::Restaurant),
+    (KeyCode::F, BuildingType::Forum),
+    (KeyCode::C, BuildingType::Cinema),
+    (KeyCode::O, BuildingType::Hospital),
+    (KeyCode::P, BuildingType::Pool),
+    (KeyCode::E, BuildingType::Creative),
+];
+/// The keys spawning decorations, which have no cooldown.
+const DECORATIONS: [(KeyCode, BuildingType); 2] = [
+    (KeyCode::T, BuildingType::Tree),
+    (KeyCode::L, BuildingType::Lamp),
+];
+
+/// The most buildings of a type that can be kept in stock, waiting to be placed.
+pub const STOCK_LIMIT: u32 = 4;
+
+/// The cooldown of a building type, and how many are ready to be spawned.
+/// The cooldown runs from the unlock of the type until the stock is full
+#[derive(Debug)]
+pub struct Cooldown {
+    pub timer: Timer,
+    pub stock: u32,
 }
 
-// Cooldowns for each non-decorative building
-
-#[derive(Resource, Debug)]
-struct HouseTimer(Timer);
-
-#[derive(Resource, Debug)]
-struct ForumTimer(Timer);
-
+/// Cooldowns for each non-decorative building.
 #[derive(Resource, Debug)]
-struct CinemaTimer(Timer);
-
-#[derive(Resource, Debug)]
-struct HospitalTimer(Timer);
-
-#[derive(Resource, Debug)]
-struct PoolTimer(Timer);
+pub struct BuildingCooldowns(pub HashMap<BuildingType, Cooldown>);
+
+impl Default for BuildingCooldowns {
+    fn default() -> Self {
+        BuildingCooldowns(
+            TOOLBAR
+                .iter()
+                .map(|(_, b_type)| {
+                    let cooldown = Cooldown {
+                        timer: Timer::from_seconds(base_cooldown(*b_type), TimerMode::Repeating),
+                        stock: 0,
+                    };
+                    (*b_type, cooldown)
+                })
+                .collect(),
+        )
+    }
+}
 
+/// The building types the player is allowed to spawn.
 #[derive(Resource, Debug)]
-struct RestaurantTimer(Timer);
+pub struct UnlockedBuildings(pub HashSet<BuildingType>);
 
-#[derive(Resource, Debug)]
-struct CreativeTimer(Timer);
+impl Default for UnlockedBuildings {
+    fn default() -> Self {
+        UnlockedBuildings(BuildingType::ALL.into_iter().collect())
+    }
+}
 
 #[derive(Component, Debug)]
 struct BuildingInfoText;
 
-#[derive(Debug, Component, PartialEq)]
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BuildingType {
     House,
     Forum,
@@ -75,34 +106,69 @@ pub enum BuildingType {
     Lamp,
 }
 
+impl BuildingType {
+    pub const ALL: [BuildingType; 9] = [
+        BuildingType::House,
+        BuildingType::Forum,
+        BuildingType::Cinema,
+        BuildingType::Hospital,
+        BuildingType::Pool,
+        BuildingType::Restaurant,
+        BuildingType::Creative,
+        BuildingType::Tree,
+        BuildingType::Lamp,
+    ];
+
+    /// The name used for this type in game files
+    pub fn id(self) -> &'static str {
+        match self {
+            BuildingType::House => "house",
+            BuildingType::Forum => "forum",
+            BuildingType::Cinema => "cinema",
+            BuildingType::Hospital => "hospital",
+            BuildingType::Pool => "pool",
+            BuildingType::Restaurant => "restaurant",
+            BuildingType::Creative => "creative",
+            BuildingType::Tree => "tree",
+            BuildingType::Lamp => "lamp",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        BuildingType::ALL
+            .into_iter()
+            .find(|b_type| b_type.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BuildingType::House => "House",
+            BuildingType::Forum => "Forum",
+            BuildingType::Cinema => "Cinema",
+            BuildingType::Hospital => "Hospital",
+            BuildingType::Pool => "Pool",
+            BuildingType::Restaurant => "Restaurant",
+            BuildingType::Creative => "Creative supplies",
+            BuildingType::Tree => "Tree",
+            BuildingType::Lamp => "Lamp",
+        }
+    }
+}
+
 #[derive(Bundle)]
 struct BuildingBundle {
     b_type: BuildingType,
     sprite: SpriteBundle,
     draggable: Draggable,
+    y_sorted: YSorted,
 }
 
 pub struct BuildingPlugin;
 
 impl Plugin for BuildingPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(BuildingAvailable { ..default() })
-            .insert_resource(HouseTimer(Timer::from_seconds(11.0, TimerMode::Repeating)))
-            .insert_resource(ForumTimer(Timer::from_seconds(55.0, TimerMode::Repeating)))
-            .insert_resource(CinemaTimer(Timer::from_seconds(99.0, TimerMode::Repeating)))
-            .insert_resource(HospitalTimer(Timer::from_seconds(
-                70.0,
-                TimerMode::Repeating,
-            )))
-            .insert_resource(PoolTimer(Timer::from_seconds(109.0, TimerMode::Repeating)))
-            .insert_resource(RestaurantTimer(Timer::from_seconds(
-                27.0,
-                TimerMode::Repeating,
-            )))
-            .insert_resource(CreativeTimer(Timer::from_seconds(
-                86.0,
-                TimerMode::Repeating,
-            )))
+        app.init_resource::<BuildingCooldowns>()
+            .init_resource::<UnlockedBuildings>()
             .add_systems(OnEnter(GameState::Playing), spawn_info_text)
             .add_systems(
                 Update,
@@ -111,7 +177,10 @@ impl Plugin for BuildingPlugin {
                     trigger_spawn,
                     hitbox_follow,
                     destack_buildings,
+                    check_placement.before(dragging_system),
                 )
+                    .chain()
+                    .after(start_pasting)
                     .run_if(in_state(GameState::Playing)),
             )
             .add_systems(
@@ -127,23 +196,19 @@ fn spawn_info_text(mut commands: Commands) {
         ..default()
     };
 
+    let mut sections = vec![];
+    for (i, (key, b_type)) in TOOLBAR.iter().enumerate() {
+        if i > 0 {
+            sections.push(TextSection::new("/", text_style.clone()));
+        }
+        sections.push(TextSection::new(
+            toolbar_label(*key, *b_type),
+            text_style.clone(),
+        ));
+    }
+
     commands.spawn((
-        TextBundle::from_sections([
-            TextSection::new("H: House", text_style.clone()),
-            TextSection::new("/", text_style.clone()),
-            TextSection::new("R: Restaurant", text_style.clone()),
-            TextSection::new("/", text_style.clone()),
-            TextSection::new("F: Forum", text_style.clone()),
-            TextSection::new("/", text_style.clone()),
-            TextSection::new("C: Cinema", text_style.clone()),
-            TextSection::new("/", text_style.clone()),
-            TextSection::new("O: Hospital", text_style.clone()),
-            TextSection::new("/", text_style.clone()),
-            TextSection::new("P: Pool", text_style.clone()),
-            TextSection::new("/", text_style.clone()),
-            TextSection::new("E: Creative supplies", text_style),
-        ])
-        .with_style(Style {
+        TextBundle::from_sections(sections).with_style(Style {
             position_type: PositionType::Absolute,
             bottom: Val::VMin(1.0),
             justify_content: JustifyContent::Center,
@@ -154,150 +219,198 @@ fn spawn_info_text(mut commands: Commands) {
     ));
 }
 
-/// If a building is available to be spawned, turn the
-/// corresponding text green, else leave it red
+/// The toolbar entry of a building type, with its key and price
+fn toolbar_label(key: KeyCode, b_type: BuildingType) -> String {
+    format!("{:?}: {} ${}", key, b_type.name(), placement_cost(b_type))
+}
+
+/// If a building is in stock, turn the
+/// corresponding text green, else leave it red. Buildings ready but
+/// too expensive are orange. Locked buildings are grey, with what
+/// it takes to unlock them
 fn update_info_text(
     mut text_query: Query<&mut Text, With<BuildingInfoText>>,
-    available: Res<BuildingAvailable>,
+    cooldowns: Res<BuildingCooldowns>,
+    unlocked: Res<UnlockedBuildings>,
+    tech_tree: Res<TechTree>,
+    treasury: Res<Treasury>,
+    palette: Res<Palette>,
 ) {
     let mut text = text_query.single_mut();
 
-    let red = TextStyle {
-        font_size: TEXT_SIZE,
-        color: Color::RED,
-        ..default()
-    };
-    let green = TextStyle {
-        font_size: TEXT_SIZE,
-        color: Color::GREEN,
-        ..default()
-    };
-
-    if available.house {
-        text.sections[0].style = green.clone();
-    } else {
-        text.sections[0].style = red.clone();
-    }
-    if available.restaurant {
-        text.sections[2].style = green.clone();
-    } else {
-        text.sections[2].style = red.clone();
-    }
-    if available.forum {
-        text.sections[4].style = green.clone();
-    } else {
-        text.sections[4].style = red.clone();
-    }
-    if available.cinema {
-        text.sections[6].style = green.clone();
-    } else {
-        text.sections[6].style = red.clone();
-    }
-    if available.hospital {
-        text.sections[8].style = green.clone();
-    } else {
-        text.sections[8].style = red.clone();
-    }
-    if available.pool {
-        text.sections[10].style = green.clone();
-    } else {
-        text.sections[10].style = red.clone();
-    }
-    if available.creative {
-        text.sections[12].style = green;
-    } else {
-        text.sections[12].style = red;
+    for (i, (key, b_type)) in TOOLBAR.iter().enumerate() {
+        let section = &mut text.sections[2 * i];
+        let label = toolbar_label(*key, *b_type);
+        if unlocked.0.contains(b_type) {
+            let stock = cooldowns.0.get(b_type).map_or(0, |c| c.stock);
+            section.style.color = match (stock > 0, treasury.can_afford(*b_type)) {
+                (true, true) => palette.good(),
+                (true, false) => palette.warning(),
+                (false, _) => palette.bad(),
+            };
+            section.value = match stock > 1 {
+                true => format!("{} x{}", label, stock),
+                false => label,
+            };
+        } else {
+            let condition = match unlock_condition(*b_type) {
+                Some(condition) if tech_tree.active => condition.description(),
+                _ => "Locked".to_string(),
+            };
+            section.style.color = Color::DARK_GRAY;
+            section.value = format!("{} ({})", label, condition);
+        }
     }
 }
 
-/// Trigger the spawning of a building and reset its cooldown, if available
-fn trigger_spawn(
+/// Refill building stocks as cooldowns finish, and trigger the spawning
+/// of a building if one is in stock and the city can pay for it
+#[allow(clippy::too_many_arguments)]
+pub fn trigger_spawn(
     mut commands: Commands,
     asset_handles: Res<AssetHandles>,
     keys: Res<Input<KeyCode>>,
     time: Res<Time>,
-    mut available: ResMut<BuildingAvailable>,
-    mut t_house: ResMut<HouseTimer>,
-    mut t_forum: ResMut<ForumTimer>,
-    mut t_cinema: ResMut<CinemaTimer>,
-    mut t_hospital: ResMut<HospitalTimer>,
-    mut t_pool: ResMut<PoolTimer>,
-    mut t_restaurant: ResMut<RestaurantTimer>,
-    mut t_creative: ResMut<CreativeTimer>,
+    mut cooldowns: ResMut<BuildingCooldowns>,
+    unlocked: Res<UnlockedBuildings>,
+    mut treasury: ResMut<Treasury>,
+    mut notifications: EventWriter<Notification>,
+    mut actions: EventWriter<ActionDone>,
 ) {
-    if !available.house {
-        t_house.0.tick(time.delta());
-    }
-    if !available.forum {
-        t_forum.0.tick(time.delta());
-    }
-    if !available.cinema {
-        t_cinema.0.tick(time.delta());
-    }
-    if !available.hospital {
-        t_hospital.0.tick(time.delta());
-    }
-    if !available.pool {
-        t_pool.0.tick(time.delta());
-    }
-    if !available.restaurant {
-        t_restaurant.0.tick(time.delta());
+    // Locked types wait for their unlock to start their cooldown
+    for (b_type, cooldown) in cooldowns.0.iter_mut() {
+        if cooldown.stock < STOCK_LIMIT && unlocked.0.contains(b_type) {
+            let finished = cooldown.timer.tick(time.delta()).times_finished_this_tick();
+            if cooldown.stock == 0 && finished > 0 {
+                notifications.send(Notification::new(
+                    Category::Building,
+                    format!("{} back in stock", b_type.name()),
+                ));
+            }
+            cooldown.stock = (cooldown.stock + finished).min(STOCK_LIMIT);
+        }
     }
-    if !available.creative {
-        t_creative.0.tick(time.delta());
+    // Leave Ctrl shortcuts alone
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
     }
 
-    available.house = t_house.0.just_finished();
-    available.forum = t_forum.0.just_finished();
-    available.cinema = t_cinema.0.just_finished();
-    available.hospital = t_hospital.0.just_finished();
-    available.pool = t_pool.0.just_finished();
-    available.restaurant = t_restaurant.0.just_finished();
-    available.creative = t_creative.0.just_finished();
-
-    if keys.just_pressed(KeyCode::H) && available.house {
-        spawn_building(BuildingType::House, &mut commands, &asset_handles);
-        available.house = false;
-    }
-    if keys.just_pressed(KeyCode::F) && available.forum {
-        spawn_building(BuildingType::Forum, &mut commands, &asset_handles);
-        available.forum = false;
-    }
-    if keys.just_pressed(KeyCode::C) && available.cinema {
-        spawn_building(BuildingType::Cinema, &mut commands, &asset_handles);
-        available.cinema = false;
-    }
-    if keys.just_pressed(KeyCode::O) && available.hospital {
-        spawn_building(BuildingType::Hospital, &mut commands, &asset_handles);
-        available.hospital = false;
-    }
-    if keys.just_pressed(KeyCode::P) && available.pool {
-        spawn_building(BuildingType::Pool, &mut commands, &asset_handles);
-        available.pool = false;
-    }
-    if keys.just_pressed(KeyCode::R) && available.restaurant {
-        spawn_building(BuildingType::Restaurant, &mut commands, &asset_handles);
-        available.restaurant = false;
+    for (key, b_type) in TOOLBAR {
+        if !keys.just_pressed(key) || !unlocked.0.contains(&b_type) {
+            continue;
+        }
+        let Some(cooldown) = cooldowns.0.get_mut(&b_type) else {
+            continue;
+        };
+        if cooldown.stock == 0 {
+            continue;
+        }
+        if let Some(paid) = buy(b_type, &mut treasury, &mut notifications) {
+            let position = spawn_position(b_type);
+            let entity = spawn_building(b_type, position, &mut commands, &asset_handles);
+            commands.entity(entity).insert(paid);
+            actions.send(placed(entity, b_type, position));
+            cooldown.stock -= 1;
+        }
     }
-    if keys.just_pressed(KeyCode::E) && available.creative {
-        spawn_building(BuildingType::Creative, &mut commands, &asset_handles);
-        available.creative = false;
+    for (key, b_type) in DECORATIONS {
+        if !keys.just_pressed(key) || !unlocked.0.contains(&b_type) {
+            continue;
+        }
+        if let Some(paid) = buy(b_type, &mut treasury, &mut notifications) {
+            let position = spawn_position(b_type);
+            let entity = spawn_building(b_type, position, &mut commands, &asset_handles);
+            commands.entity(entity).insert(paid);
+            actions.send(placed(entity, b_type, position));
+        }
     }
-    if keys.just_pressed(KeyCode::T) {
-        spawn_building(BuildingType::Tree, &mut commands, &asset_handles);
+}
+
+/// The history entry of a building the player just spawned
+fn placed(entity: Entity, b_type: BuildingType, position: Vec2) -> ActionDone {
+    ActionDone(Action::Placed {
+        entity,
+        b_type,
+        transform: Transform::from_translation(position.extend(STANDING_LEVEL)),
+    })
+}
+
+/// Pay for a building, or tell the player the city can't afford it
+fn buy(
+    b_type: BuildingType,
+    treasury: &mut Treasury,
+    notifications: &mut EventWriter<Notification>,
+) -> Option<Paid> {
+    let paid = treasury.buy(b_type);
+    if paid.is_none() {
+        notifications.send(Notification::new(
+            Category::Building,
+            format!(
+                "Not enough funds for a {} (${})",
+                b_type.name(),
+                placement_cost(b_type)
+            ),
+        ));
     }
-    if keys.just_pressed(KeyCode::L) {
-        spawn_building(BuildingType::Lamp, &mut commands, &asset_handles);
+    paid
+}
+
+/// The time it takes for a building to be added to the stock, in seconds
+fn base_cooldown(b_type: BuildingType) -> f32 {
+    match b_type {
+        BuildingType::House => 11.0,
+        BuildingType::Forum => 55.0,
+        BuildingType::Cinema => 99.0,
+        BuildingType::Hospital => 70.0,
+        BuildingType::Pool => 109.0,
+        BuildingType::Restaurant => 27.0,
+        BuildingType::Creative => 86.0,
+        // Decorations are always in stock
+        BuildingType::Tree | BuildingType::Lamp => 0.0,
     }
 }
 
-/// Spawn a building of the given type
-fn spawn_building(
+/// New buildings appear with their bottom left corner on the origin
+fn spawn_position(b_type: BuildingType) -> Vec2 {
+    get_size_from_type(&b_type) / 2.0
+}
+
+/// Spawn a building of the given type, centered on the given position
+pub fn spawn_building(
     b_type: BuildingType,
+    position: Vec2,
     commands: &mut Commands,
     asset_handles: &Res<AssetHandles>,
-) {
-    let handle = match b_type {
+) -> Entity {
+    let size = get_size_from_type(&b_type);
+
+    commands
+        .spawn(BuildingBundle {
+            b_type,
+            sprite: SpriteBundle {
+                texture: building_texture(b_type, asset_handles),
+                transform: Transform {
+                    translation: position.extend(STANDING_LEVEL),
+                    ..default()
+                },
+                ..default()
+            },
+            draggable: Draggable {
+                interact: Interactable {
+                    bottom_left: position - size / 2.0,
+                    top_right: position + size / 2.0,
+                },
+                being_dragged: false,
+                drag_origin: Vec2::ZERO,
+                grab_offset: Vec2::ZERO,
+            },
+            y_sorted: YSorted::default(),
+        })
+        .id()
+}
+
+pub fn building_texture(b_type: BuildingType, asset_handles: &AssetHandles) -> Handle<Image> {
+    match b_type {
         BuildingType::House => asset_handles.house.clone(),
         BuildingType::Forum => asset_handles.forum.clone(),
         BuildingType::Cinema => asset_handles.cinema.clone(),
@@ -307,33 +420,49 @@ fn spawn_building(
         BuildingType::Creative => asset_handles.creative.clone(),
         BuildingType::Tree => asset_handles.tree.clone(),
         BuildingType::Lamp => asset_handles.lamp.clone(),
-    };
-    let top_right = get_size_from_type(&b_type);
+    }
+}
 
-    commands.spawn(BuildingBundle {
-        b_type,
-        sprite: SpriteBundle {
-            texture: handle,
-            transform: Transform {
-                translation: Vec3::new(top_right.x / 2.0, top_right.y / 2.0, BUILDING_LEVEL),
-                ..default()
-            },
-            ..default()
-        },
-        draggable: Draggable {
-            interact: Interactable {
-                bottom_left: Vec2::ZERO,
-                top_right,
-            },
-            being_dragged: false,
-        },
-    });
+/// Tint dragged buildings red where they can't be built, and send
+/// them all back where they were picked up if one is dropped there anyway
+fn check_placement(
+    mut buildings_query: Query<(&Draggable, &BuildingType, &mut Transform, &mut Sprite)>,
+    buttons: Res<Input<MouseButton>>,
+    ground: Res<Ground>,
+) {
+    let mut all_valid = true;
+    for (draggable, b_type, transform, mut sprite) in &mut buildings_query {
+        if !draggable.being_dragged {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let valid = ground.can_build(*b_type, position, footprint(*b_type, &transform));
+        all_valid &= valid;
+        sprite.color = match valid {
+            true => Color::WHITE,
+            false => Color::rgb(1.0, 0.4, 0.4),
+        };
+    }
+
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    // A group is dropped as a whole, or not at all
+    for (draggable, _b_type, mut transform, mut sprite) in &mut buildings_query {
+        if draggable.being_dragged {
+            if !all_valid {
+                transform.translation.x = draggable.drag_origin.x;
+                transform.translation.y = draggable.drag_origin.y;
+            }
+            sprite.color = Color::WHITE;
+        }
+    }
 }
 
 /// Make a building's hitbox follow its translation
 fn hitbox_follow(mut draggables_query: Query<(&mut Draggable, &BuildingType, &Transform)>) {
     for (mut draggable, b_type, transform) in &mut draggables_query {
-        let building_size = get_size_from_type(b_type);
+        let building_size = footprint(*b_type, transform);
         draggable.interact.bottom_left.x = transform.translation.x - building_size.x / 2.0;
         draggable.interact.bottom_left.y = transform.translation.y - building_size.y / 2.0;
         draggable.interact.top_right.x = transform.translation.x + building_size.x / 2.0;
@@ -341,7 +470,7 @@ fn hitbox_follow(mut draggables_query: Query<(&mut Draggable, &BuildingType, &Tr
     }
 }
 
-fn get_size_from_type(b_type: &BuildingType) -> Vec2 {
+pub fn get_size_from_type(b_type: &BuildingType) -> Vec2 {
     match b_type {
         BuildingType::House => HOUSE_SIZE,
         BuildingType::Forum => FORUM_SIZE,
@@ -355,6 +484,18 @@ fn get_size_from_type(b_type: &BuildingType) -> Vec2 {
     }
 }
 
+/// The size a building covers on the ground, which is
+/// its sprite's size turned by its rotation
+pub fn footprint(b_type: BuildingType, transform: &Transform) -> Vec2 {
+    let size = get_size_from_type(&b_type);
+    // A quarter turn sends the X axis close to the Y axis
+    let quarter_turned = (transform.rotation * Vec3::X).x.abs() < 0.5;
+    match quarter_turned {
+        true => size.yx(),
+        false => size,
+    }
+}
+
 /// Prevent two buildings from being on top of eachother
 fn destack_buildings(
     mut buildings_query: Query<(&BuildingType, &mut Transform)>,
@@ -385,3 +526,68 @@ fn cleanup_text(mut commands: Commands, text_query: Query<Entity, With<BuildingI
         commands.entity(entity).despawn_recursive();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{test_app, TEST_TICK};
+
+    fn stacked_app() -> (App, Entity, Entity) {
+        let mut app = test_app();
+        app.init_resource::<Input<MouseButton>>()
+            .add_systems(Update, destack_buildings);
+        let first = app
+            .world
+            .spawn((BuildingType::House, Transform::default()))
+            .id();
+        let second = app
+            .world
+            .spawn((BuildingType::Restaurant, Transform::from_xyz(5.0, 0.0, 0.0)))
+            .id();
+        (app, first, second)
+    }
+
+    fn distance(app: &App, first: Entity, second: Entity) -> f32 {
+        let position = |entity| app.world.get::<Transform>(entity).unwrap().translation;
+        position(first).distance(position(second))
+    }
+
+    #[test]
+    fn destack_separates_overlapping_buildings() {
+        let (mut app, first, second) = stacked_app();
+        app.update();
+        assert!(distance(&app, first, second) >= MIN_DISTANCE);
+    }
+
+    #[test]
+    fn destack_waits_for_the_drop() {
+        let (mut app, first, second) = stacked_app();
+        app.world
+            .resource_mut::<Input<MouseButton>>()
+            .press(MouseButton::Left);
+        app.update();
+        assert_eq!(distance(&app, first, second), 5.0);
+    }
+
+    #[test]
+    fn locked_types_do_not_stock_up() {
+        let mut app = test_app();
+        app.init_resource::<AssetHandles>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<BuildingCooldowns>()
+            .insert_resource(UnlockedBuildings(HashSet::from([BuildingType::House])))
+            .init_resource::<Treasury>()
+            .add_event::<Notification>()
+            .add_event::<ActionDone>()
+            .add_systems(Update, trigger_spawn);
+        for cooldown in app.world.resource_mut::<BuildingCooldowns>().0.values_mut() {
+            cooldown.timer = Timer::from_seconds(TEST_TICK, TimerMode::Repeating);
+        }
+        for _ in 0..10 {
+            app.update();
+        }
+        let cooldowns = app.world.resource::<BuildingCooldowns>();
+        assert_eq!(cooldowns.0[&BuildingType::House].stock, STOCK_LIMIT);
+        assert_eq!(cooldowns.0[&BuildingType::Forum].stock, 0);
+    }
+}
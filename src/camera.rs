@@ -6,6 +6,10 @@ use crate::states::GameState;
 const CAMERA_LEVEL: f32 = 20.0;
 const MOVEMENT_VAL: f32 = 50.0;
 
+/// Marker for the camera the player looks through.
+#[derive(Component, Debug)]
+pub struct MainCamera;
+
 /// The position of the cursor, in world coordinates.
 #[derive(Resource, Debug, Default)]
 pub struct CursorPosition(pub Vec2);
@@ -16,7 +20,8 @@ impl Plugin for Camera2dPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CursorPosition>()
             .add_systems(Startup, spawn_camera)
-            .add_systems(Update, get_cursor_pos)
+            // Before anything uses it, so that the whole frame sees the same position
+            .add_systems(First, get_cursor_pos)
             .add_systems(
                 Update,
                 (zoom_camera, move_camera).run_if(in_state(GameState::Playing)),
@@ -25,24 +30,30 @@ impl Plugin for Camera2dPlugin {
 }
 
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle {
-        transform: Transform {
-            translation: Vec3::new(0.0, 0.0, CAMERA_LEVEL),
-            scale: Vec3::ONE,
+    commands.spawn((
+        Camera2dBundle {
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, CAMERA_LEVEL),
+                scale: Vec3::ONE,
+                ..default()
+            },
             ..default()
         },
-        ..default()
-    });
+        MainCamera,
+    ));
 }
 
 fn get_cursor_pos(
     mut pos: ResMut<CursorPosition>,
-    camera_query: Query<(&Camera, &GlobalTransform)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     window_query: Query<&Window, With<PrimaryWindow>>,
 ) {
     let (camera, camera_transform) = camera_query.single();
 
-    let window = window_query.single();
+    // Headless runs have no window
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
 
     if let Some(world_position) = window
         .cursor_position()
@@ -53,10 +64,13 @@ fn get_cursor_pos(
     }
 }
 
-fn zoom_camera(mut camera_query: Query<&mut Transform, With<Camera2d>>, keys: Res<Input<KeyCode>>) {
+fn zoom_camera(
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+    keys: Res<Input<KeyCode>>,
+) {
     let mut camera_transform = camera_query
         .get_single_mut()
-        .expect("More than one Camera2d!");
+        .expect("More than one MainCamera!");
 
     if keys.just_pressed(KeyCode::PageUp) {
         camera_transform.scale += Vec3::new(0.2, 0.2, 0.0);
@@ -67,7 +81,7 @@ fn zoom_camera(mut camera_query: Query<&mut Transform, With<Camera2d>>, keys: Re
 }
 
 fn move_camera(
-    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
     keys: Res<Input<KeyCode>>,
     time: Res<Time>,
 ) {
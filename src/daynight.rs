@@ -0,0 +1,287 @@
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::HashMap,
+};
+
+use crate::{
+    building::BuildingType,
+    debug::TEXT_SIZE,
+    ground::{TILES_RANGE_X, TILES_RANGE_Y, TILE_SIZE},
+    states::GameState,
+};
+
+/// The length of a whole day, in seconds.
+const DAY_LENGTH: f32 = 240.0;
+/// The hour games start at.
+const START_HOUR: f32 = 8.0;
+/// How dark the deepest night gets, from 0 (not at all) to 1 (black).
+const MAX_DARKNESS: f32 = 0.6;
+/// The radius lamps light at night.
+pub const LAMP_RADIUS: f32 = 72.0;
+/// How bright a lamp's light is at the deepest night, as an alpha.
+const LIGHT_STRENGTH: f32 = 0.3;
+const NIGHT_COLOR: Color = Color::rgb(0.02, 0.02, 0.12);
+const LIGHT_COLOR: Color = Color::rgb(1.0, 0.85, 0.45);
+
+// for z-ordering, over everything standing but under emotes
+const NIGHT_LEVEL: f32 = 5.0;
+const LIGHT_LEVEL: f32 = 5.5;
+
+/// The time of day in the city, reset when a game starts.
+#[derive(Resource, Debug)]
+pub struct Clock {
+    /// Days since the game started, from 1
+    pub day: u32,
+    /// From 0 to 24
+    pub hour: f32,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            day: 1,
+            hour: START_HOUR,
+        }
+    }
+}
+
+impl Clock {
+    pub fn phase(&self) -> DayPhase {
+        match self.hour {
+            hour if (6.0..12.0).contains(&hour) => DayPhase::Morning,
+            hour if (12.0..18.0).contains(&hour) => DayPhase::Afternoon,
+            hour if (18.0..22.0).contains(&hour) => DayPhase::Evening,
+            _ => DayPhase::Night,
+        }
+    }
+
+    /// How dark it is, from 0 in full daylight to 1 at the deepest night
+    pub fn darkness(&self) -> f32 {
+        match self.hour {
+            // Dusk
+            hour if (18.0..21.0).contains(&hour) => (hour - 18.0) / 3.0,
+            // Dawn
+            hour if (5.0..7.0).contains(&hour) => 1.0 - (hour - 5.0) / 2.0,
+            hour if (7.0..18.0).contains(&hour) => 0.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// A part of the day, which changes what people need.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DayPhase {
+    Morning,
+    Afternoon,
+    Evening,
+    Night,
+}
+
+impl DayPhase {
+    fn label(self) -> &'static str {
+        match self {
+            DayPhase::Morning => "Morning",
+            DayPhase::Afternoon => "Afternoon",
+            DayPhase::Evening => "Evening",
+            DayPhase::Night => "Night",
+        }
+    }
+
+    /// How much shelter people lose each second: they want a home at night,
+    /// though lit streets make them feel safer
+    pub fn shelter_decay(self, lit: bool) -> f32 {
+        match (self, lit) {
+            (DayPhase::Night, false) => 2.0,
+            (DayPhase::Night, true) => 1.5,
+            _ => 1.0,
+        }
+    }
+
+    /// The chance people get bored each second, higher in the evening
+    pub fn boredom_chance(self) -> f64 {
+        match self {
+            DayPhase::Evening => 0.9,
+            _ => 0.45,
+        }
+    }
+}
+
+/// Returns `true` if the position is lit by one of the lamps at night
+pub fn is_lit(position: Vec3, lamps: &[Vec3]) -> bool {
+    lamps
+        .iter()
+        .any(|lamp| lamp.truncate().distance(position.truncate()) < LAMP_RADIUS)
+}
+
+/// Shared by all lamp lights, so that they brighten together as night falls.
+#[derive(Resource, Debug)]
+struct LightAssets {
+    mesh: Mesh2dHandle,
+    material: Handle<ColorMaterial>,
+}
+
+/// The shade covering the map at night.
+#[derive(Component, Debug)]
+struct Night;
+
+#[derive(Component, Debug)]
+struct LampLight {
+    lamp: Entity,
+}
+
+#[derive(Component, Debug)]
+struct ClockText;
+
+pub struct DayNightPlugin;
+
+impl Plugin for DayNightPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Clock>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (reset_clock, spawn_night, spawn_clock_text),
+            )
+            .add_systems(
+                Update,
+                (tick_clock, update_night, light_lamps, update_clock_text)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_night);
+    }
+}
+
+fn reset_clock(mut clock: ResMut<Clock>) {
+    *clock = Clock::default();
+}
+
+fn spawn_night(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let map_size = Vec2::new(
+        TILES_RANGE_X.len() as f32 * TILE_SIZE.x,
+        TILES_RANGE_Y.len() as f32 * TILE_SIZE.y,
+    );
+    let map_center = Vec2::new(
+        TILES_RANGE_X.start as f32 * TILE_SIZE.x,
+        TILES_RANGE_Y.start as f32 * TILE_SIZE.y,
+    ) + map_size / 2.0;
+
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: NIGHT_COLOR.with_a(0.0),
+                custom_size: Some(map_size),
+                ..default()
+            },
+            transform: Transform::from_translation(map_center.extend(NIGHT_LEVEL)),
+            ..default()
+        },
+        Night,
+    ));
+    commands.insert_resource(LightAssets {
+        mesh: meshes.add(shape::Circle::new(LAMP_RADIUS).into()).into(),
+        material: materials.add(ColorMaterial::from(LIGHT_COLOR.with_a(0.0))),
+    });
+}
+
+pub fn tick_clock(mut clock: ResMut<Clock>, time: Res<Time>) {
+    clock.hour += time.delta_seconds() * 24.0 / DAY_LENGTH;
+    if clock.hour >= 24.0 {
+        clock.hour -= 24.0;
+        clock.day += 1;
+    }
+}
+
+/// Dim the city as night falls, and brighten the lamps
+fn update_night(
+    clock: Res<Clock>,
+    light_assets: Res<LightAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut night_query: Query<&mut Sprite, With<Night>>,
+) {
+    let darkness = clock.darkness();
+    for mut sprite in &mut night_query {
+        sprite.color.set_a(darkness * MAX_DARKNESS);
+    }
+    if let Some(material) = materials.get_mut(&light_assets.material) {
+        material.color.set_a(darkness * LIGHT_STRENGTH);
+    }
+}
+
+/// Give every lamp a light following it, and put out those of removed lamps
+fn light_lamps(
+    mut commands: Commands,
+    light_assets: Res<LightAssets>,
+    lamps_query: Query<(Entity, &BuildingType, &Transform)>,
+    mut lights_query: Query<(Entity, &LampLight, &mut Transform), Without<BuildingType>>,
+) {
+    let mut lamps: HashMap<Entity, Vec2> = lamps_query
+        .iter()
+        .filter(|(_, b_type, _)| **b_type == BuildingType::Lamp)
+        .map(|(entity, _, transform)| (entity, transform.translation.truncate()))
+        .collect();
+
+    for (entity, light, mut transform) in &mut lights_query {
+        match lamps.remove(&light.lamp) {
+            Some(position) => transform.translation = position.extend(LIGHT_LEVEL),
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+    for (lamp, position) in lamps {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: light_assets.mesh.clone(),
+                material: light_assets.material.clone(),
+                transform: Transform::from_translation(position.extend(LIGHT_LEVEL)),
+                ..default()
+            },
+            LampLight { lamp },
+        ));
+    }
+}
+
+fn spawn_clock_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: TEXT_SIZE - 6.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            // Under the score
+            right: Val::VMax(1.0),
+            top: Val::VMin(4.5),
+            ..default()
+        }),
+        ClockText,
+    ));
+}
+
+fn update_clock_text(clock: Res<Clock>, mut text_query: Query<&mut Text, With<ClockText>>) {
+    let minutes = (clock.hour * 60.0) as u32;
+    text_query.single_mut().sections[0].value = format!(
+        "Day {}, {:02}:{:02} ({})",
+        clock.day,
+        minutes / 60,
+        minutes % 60,
+        clock.phase().label()
+    );
+}
+
+#[allow(clippy::type_complexity)]
+fn cleanup_night(
+    mut commands: Commands,
+    night_query: Query<Entity, Or<(With<Night>, With<LampLight>, With<ClockText>)>>,
+) {
+    for entity in &night_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<LightAssets>();
+}
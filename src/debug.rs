@@ -1,3 +1,6 @@
+// Only plugged in by hand while debugging, see `main`
+#![allow(dead_code)]
+
 use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
 
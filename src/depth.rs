@@ -0,0 +1,55 @@
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::{
+    building::{footprint, BuildingType},
+    ground::{TILES_RANGE_Y, TILE_SIZE},
+    movement::resolve_movements,
+};
+
+/// The lowest depth of what stands on the ground, buildings and people alike.
+pub const STANDING_LEVEL: f32 = 1.0;
+/// How much depth standing things are spread over, from the top of the map to its bottom.
+const STANDING_DEPTH: f32 = 1.0;
+
+/// Gives an entity a depth following the height of its base,
+/// so that what stands lower on screen is drawn in front.
+#[derive(Component, Debug, Default)]
+pub struct YSorted {
+    /// From the entity's center down to its base. Buildings use their footprint instead
+    pub base: f32,
+}
+
+pub struct DepthPlugin;
+
+impl Plugin for DepthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            // After moving, so that people always meet with the same depths
+            y_sort
+                .after(resolve_movements)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// The depth of something standing on the given height
+pub fn standing_depth(base_y: f32) -> f32 {
+    let map_bottom = TILES_RANGE_Y.start as f32 * TILE_SIZE.y;
+    let map_height = TILES_RANGE_Y.len() as f32 * TILE_SIZE.y;
+    let from_bottom = ((base_y - map_bottom) / map_height).clamp(0.0, 1.0);
+    STANDING_LEVEL + (1.0 - from_bottom) * STANDING_DEPTH
+}
+
+fn y_sort(
+    mut sorted_query: Query<(&YSorted, &mut Transform, Option<&BuildingType>), Changed<Transform>>,
+) {
+    for (sorted, mut transform, b_type) in &mut sorted_query {
+        let base = b_type.map_or(sorted.base, |b_type| footprint(*b_type, &transform).y / 2.0);
+        let depth = standing_depth(transform.translation.y - base);
+        // Only write when needed, to keep change detection meaningful
+        if transform.translation.z != depth {
+            transform.translation.z = depth;
+        }
+    }
+}
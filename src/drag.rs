@@ -1,12 +1,27 @@
-use bevy::prelude::*;
+use bevy::{input::InputSystem, prelude::*};
 
-use crate::{camera::CursorPosition, states::GameState};
+use crate::{
+    camera::CursorPosition, person::Person, replay::replay_inputs, selection::Selected,
+    states::GameState,
+};
 
 /// Component for all Draggable entities.
 #[derive(Debug, Default, Component)]
 pub struct Draggable {
     pub interact: Interactable,
     pub being_dragged: bool,
+    /// Where the entity was when it got picked up
+    pub drag_origin: Vec2,
+    /// Where the entity is relative to the cursor while dragged
+    pub grab_offset: Vec2,
+}
+
+/// Sent when a dragged entity is dropped somewhere else than where it was picked up.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Dropped {
+    pub entity: Entity,
+    pub from: Vec2,
+    pub to: Vec2,
 }
 
 /// Component for all click-interactable entities.
@@ -16,41 +31,100 @@ pub struct Interactable {
     pub top_right: Vec2,
 }
 
+/// The entity drawn on top of the others under the cursor, among draggables and people.
+/// Whatever reacts to clicks looks here, so that it agrees with what the player sees.
+#[derive(Resource, Debug, Default)]
+pub struct Hovered(pub Option<Entity>);
+
 pub struct DragPlugin;
 
 impl Plugin for DragPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, dragging_system.run_if(in_state(GameState::Playing)));
+        app.init_resource::<Hovered>()
+            .add_event::<Dropped>()
+            .add_systems(
+                PreUpdate,
+                find_hovered
+                    .after(InputSystem)
+                    .after(replay_inputs)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(Update, dragging_system.run_if(in_state(GameState::Playing)));
     }
 }
 
-fn dragging_system(
-    mut draggables_query: Query<(&mut Draggable, &mut Transform)>,
+/// Find the top-most draggable or person under the cursor
+pub fn find_hovered(
+    mut hovered: ResMut<Hovered>,
+    cursor_pos: Res<CursorPosition>,
+    draggables_query: Query<(Entity, &Draggable, &Transform)>,
+    persons_query: Query<(Entity, &Person, &Transform)>,
+) {
+    let draggables = draggables_query
+        .iter()
+        .filter(|(_, draggable, _)| clicked_on(&cursor_pos, &draggable.interact))
+        .map(|(entity, _, transform)| (entity, transform));
+    let persons = persons_query
+        .iter()
+        .filter(|(_, person, _)| clicked_on(&cursor_pos, &person.interact))
+        .map(|(entity, _, transform)| (entity, transform));
+    hovered.0 = top_most(draggables.chain(persons));
+}
+
+/// Pick up the hovered entity, or the whole selection
+/// if it is part of it, and move it with the cursor
+pub fn dragging_system(
+    mut draggables_query: Query<(Entity, &mut Draggable, &mut Transform, Has<Selected>)>,
     buttons: Res<Input<MouseButton>>,
     cursor_pos: Res<CursorPosition>,
+    hovered: Res<Hovered>,
+    mut dropped: EventWriter<Dropped>,
 ) {
     if buttons.just_pressed(MouseButton::Left) {
-        for (mut draggable, _transform) in &mut draggables_query {
-            if clicked_on(&cursor_pos, &draggable.interact) {
-                draggable.being_dragged = true
+        let grabbed = hovered
+            .0
+            .and_then(|entity| draggables_query.get(entity).ok())
+            .map(|(entity, _, _, selected)| (entity, selected));
+        if let Some((grabbed, group)) = grabbed {
+            for (entity, mut draggable, transform, selected) in &mut draggables_query {
+                if entity == grabbed || (group && selected) {
+                    draggable.being_dragged = true;
+                    draggable.drag_origin = transform.translation.truncate();
+                    draggable.grab_offset = draggable.drag_origin - cursor_pos.0;
+                }
             }
         }
     }
     if buttons.pressed(MouseButton::Left) {
-        for (draggable, mut transform) in &mut draggables_query {
+        for (_entity, draggable, mut transform, _selected) in &mut draggables_query {
             if draggable.being_dragged {
-                transform.translation.x = cursor_pos.0.x;
-                transform.translation.y = cursor_pos.0.y;
+                transform.translation.x = cursor_pos.0.x + draggable.grab_offset.x;
+                transform.translation.y = cursor_pos.0.y + draggable.grab_offset.y;
             }
         }
     }
     if buttons.just_released(MouseButton::Left) {
-        for (mut draggable, _transform) in &mut draggables_query {
+        for (entity, mut draggable, transform, _selected) in &mut draggables_query {
+            let position = transform.translation.truncate();
+            if draggable.being_dragged && position != draggable.drag_origin {
+                dropped.send(Dropped {
+                    entity,
+                    from: draggable.drag_origin,
+                    to: position,
+                });
+            }
             draggable.being_dragged = false;
         }
     }
 }
 
+/// The item whose entity is drawn on top of the others, if any
+fn top_most<'a, T>(candidates: impl Iterator<Item = (T, &'a Transform)>) -> Option<T> {
+    candidates
+        .max_by(|(_, a), (_, b)| a.translation.z.total_cmp(&b.translation.z))
+        .map(|(item, _)| item)
+}
+
 /// Returns `true` if the cursor is within the interactable's bounding box
 pub fn clicked_on(cursor_pos: &Res<CursorPosition>, interactable: &Interactable) -> bool {
     cursor_pos.0.x > interactable.bottom_left.x
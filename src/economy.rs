@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+
+use crate::{
+    building::BuildingType, debug::TEXT_SIZE, events::apply_events, palette::Palette,
+    person::Person, states::GameState, unlock::check_milestones,
+};
+
+/// The money the city starts with.
+const STARTING_FUNDS: f32 = 400.0;
+/// The taxes paid by a fully satisfied resident, per second.
+const TAX_PER_RESIDENT: f32 = 1.5;
+/// Time between two budget updates, in seconds.
+const BUDGET_PERIOD: f32 = 1.0;
+
+/// The price of building one of the given type
+pub fn placement_cost(b_type: BuildingType) -> f32 {
+    match b_type {
+        BuildingType::House => 50.0,
+        BuildingType::Restaurant => 80.0,
+        BuildingType::Forum => 150.0,
+        BuildingType::Creative => 200.0,
+        BuildingType::Cinema => 250.0,
+        BuildingType::Hospital => 300.0,
+        BuildingType::Pool => 350.0,
+        BuildingType::Tree => 10.0,
+        BuildingType::Lamp => 15.0,
+    }
+}
+
+/// What a building of the given type costs to run, per second
+pub fn upkeep(b_type: BuildingType) -> f32 {
+    match b_type {
+        BuildingType::House => 0.2,
+        BuildingType::Restaurant => 0.4,
+        BuildingType::Forum => 0.8,
+        BuildingType::Creative => 1.0,
+        BuildingType::Cinema => 1.5,
+        BuildingType::Hospital => 2.0,
+        BuildingType::Pool => 2.0,
+        BuildingType::Tree => 0.0,
+        BuildingType::Lamp => 0.05,
+    }
+}
+
+/// What the city paid for a building, part of which comes back when it is
+/// demolished. Starter and scenario buildings came for free, and have none.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Paid(pub f32);
+
+/// The city's money, reset when a game starts. The balance may go
+/// negative through upkeep, but nothing can be built until it is back up.
+#[derive(Resource, Debug)]
+pub struct Treasury {
+    pub balance: f32,
+    /// The last net income, per second
+    pub cashflow: f32,
+}
+
+impl Default for Treasury {
+    fn default() -> Self {
+        Treasury {
+            balance: STARTING_FUNDS,
+            cashflow: 0.0,
+        }
+    }
+}
+
+impl Treasury {
+    pub fn can_afford(&self, b_type: BuildingType) -> bool {
+        self.balance >= placement_cost(b_type)
+    }
+
+    /// Pay for a new building, if there is enough money for it,
+    /// returning what was paid to be put on the building
+    pub fn buy(&mut self, b_type: BuildingType) -> Option<Paid> {
+        let cost = placement_cost(b_type);
+        match self.can_afford(b_type) {
+            true => {
+                self.balance -= cost;
+                Some(Paid(cost))
+            }
+            false => None,
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+struct BudgetTimer(Timer);
+
+#[derive(Component, Debug)]
+struct TreasuryText;
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Treasury>()
+            .insert_resource(BudgetTimer(Timer::from_seconds(
+                BUDGET_PERIOD,
+                TimerMode::Repeating,
+            )))
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (reset_budget_timer, spawn_treasury_text),
+            )
+            .add_systems(
+                Update,
+                (
+                    collect_budget.after(apply_events).before(check_milestones),
+                    update_treasury_text,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_treasury_text);
+    }
+}
+
+fn reset_budget_timer(mut budget_timer: ResMut<BudgetTimer>) {
+    budget_timer.0.reset();
+}
+
+/// Collect taxes from residents, the happier the better, and pay the upkeep of every building
+fn collect_budget(
+    mut treasury: ResMut<Treasury>,
+    mut budget_timer: ResMut<BudgetTimer>,
+    persons_query: Query<&Person>,
+    buildings_query: Query<&BuildingType>,
+    time: Res<Time>,
+) {
+    if !budget_timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let income: f32 = persons_query
+        .iter()
+        .map(|person| TAX_PER_RESIDENT * person.satisfaction / 100.0)
+        .sum();
+    let expenses: f32 = buildings_query.iter().map(|b_type| upkeep(*b_type)).sum();
+
+    treasury.cashflow = income - expenses;
+    treasury.balance += treasury.cashflow * BUDGET_PERIOD;
+}
+
+fn spawn_treasury_text(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: TEXT_SIZE,
+        ..default()
+    };
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new("Funds: ", text_style.clone()),
+            TextSection::from_style(TextStyle {
+                color: Color::GOLD,
+                ..text_style.clone()
+            }),
+            TextSection::from_style(text_style),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            // Just above the building toolbar
+            left: Val::VMax(1.0),
+            bottom: Val::VMin(6.0),
+            ..default()
+        }),
+        TreasuryText,
+    ));
+}
+
+fn update_treasury_text(
+    treasury: Res<Treasury>,
+    palette: Res<Palette>,
+    mut text_query: Query<&mut Text, With<TreasuryText>>,
+) {
+    let mut text = text_query.single_mut();
+    text.sections[1].value = format!("${:.0}", treasury.balance);
+    text.sections[2].value = format!(" ({:+.1}/s)", treasury.cashflow);
+    text.sections[2].style.color = if treasury.cashflow >= 0.0 {
+        palette.good()
+    } else {
+        palette.bad()
+    };
+}
+
+fn cleanup_treasury_text(mut commands: Commands, text_query: Query<Entity, With<TreasuryText>>) {
+    for entity in &text_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
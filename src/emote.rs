@@ -0,0 +1,273 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    asset_loader::AssetHandles,
+    building::BuildingType,
+    debug::TEXT_SIZE,
+    overlay::lacks_need,
+    person::{Person, SPRITE_SIZE},
+    states::GameState,
+};
+
+/// The size of an emote bubble in the emote sheet.
+pub const EMOTE_SIZE: Vec2 = Vec2::new(12.0, 12.0);
+/// The needs emotes are drawn for, in the order of the emote sheet.
+pub const EMOTE_NEEDS: [BuildingType; 7] = [
+    BuildingType::House,
+    BuildingType::Restaurant,
+    BuildingType::Forum,
+    BuildingType::Cinema,
+    BuildingType::Hospital,
+    BuildingType::Pool,
+    BuildingType::Creative,
+];
+/// The order needs are shown in, when a person lacks several at once.
+const EMOTE_PRIORITY: [BuildingType; 7] = [
+    BuildingType::Hospital,
+    BuildingType::Restaurant,
+    BuildingType::House,
+    BuildingType::Forum,
+    BuildingType::Cinema,
+    BuildingType::Pool,
+    BuildingType::Creative,
+];
+/// The maximum number of emotes shown at once; the least satisfied people get them first.
+const MAX_EMOTES: usize = 30;
+/// How long an emote takes to fade in or out, in seconds.
+const EMOTE_FADE: f32 = 0.5;
+/// Time between two choices of who shows an emote, in seconds.
+const EMOTE_REFRESH: f32 = 0.5;
+
+// for z-ordering, over everything standing but under the selector
+const EMOTE_LEVEL: f32 = 9.0;
+
+/// Whether emotes are shown above people.
+#[derive(Resource, Debug)]
+pub struct EmotesShown(pub bool);
+
+impl Default for EmotesShown {
+    fn default() -> Self {
+        EmotesShown(true)
+    }
+}
+
+#[derive(Resource, Debug)]
+struct EmoteTimer(Timer);
+
+/// A bubble above a person, showing the need they lack.
+#[derive(Component, Debug)]
+struct Emote {
+    person: Entity,
+    need: BuildingType,
+    fading_out: bool,
+}
+
+#[derive(Component, Debug)]
+struct EmoteText;
+
+pub struct EmotePlugin;
+
+impl Plugin for EmotePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EmotesShown>()
+            .insert_resource(EmoteTimer(Timer::from_seconds(
+                EMOTE_REFRESH,
+                TimerMode::Repeating,
+            )))
+            .add_systems(OnEnter(GameState::Playing), spawn_emote_text)
+            .add_systems(
+                Update,
+                (
+                    toggle_emotes,
+                    choose_emotes,
+                    fade_emotes,
+                    follow_persons,
+                    update_emote_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_emotes);
+    }
+}
+
+/// Cut the emote sheet into its bubbles
+pub fn emote_atlas(sheet: Handle<Image>) -> TextureAtlas {
+    TextureAtlas::from_grid(sheet, EMOTE_SIZE, EMOTE_NEEDS.len(), 1, None, None)
+}
+
+/// N shows or hides emotes
+fn toggle_emotes(keys: Res<Input<KeyCode>>, mut shown: ResMut<EmotesShown>) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if keys.just_pressed(KeyCode::N) && !ctrl {
+        shown.0 = !shown.0;
+    }
+}
+
+/// The most pressing need the person lacks, if any
+fn pressing_need(person: &Person) -> Option<BuildingType> {
+    EMOTE_PRIORITY
+        .into_iter()
+        .find(|need| lacks_need(person, *need))
+}
+
+/// Give an emote to the least satisfied people lacking something,
+/// and fade out those that aren't needed anymore
+fn choose_emotes(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    shown: Res<EmotesShown>,
+    time: Res<Time>,
+    mut timer: ResMut<EmoteTimer>,
+    persons_query: Query<(Entity, &Person, &Transform)>,
+    mut emotes_query: Query<&mut Emote>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() && !shown.is_changed() {
+        return;
+    }
+
+    let mut lacking: Vec<(Entity, f32, BuildingType, Vec3)> = match shown.0 {
+        true => persons_query
+            .iter()
+            .filter_map(|(entity, person, transform)| {
+                pressing_need(person)
+                    .map(|need| (entity, person.satisfaction, need, transform.translation))
+            })
+            .collect(),
+        false => vec![],
+    };
+    lacking.sort_by(|a, b| a.1.total_cmp(&b.1));
+    lacking.truncate(MAX_EMOTES);
+    let mut wanted: HashMap<Entity, (BuildingType, Vec3)> = lacking
+        .into_iter()
+        .map(|(entity, _, need, position)| (entity, (need, position)))
+        .collect();
+
+    for mut emote in &mut emotes_query {
+        match wanted.get(&emote.person) {
+            // Already there, nothing to spawn
+            Some((need, _)) if *need == emote.need => {
+                emote.fading_out = false;
+                wanted.remove(&emote.person);
+            }
+            // Let the old one fade out before showing the new one
+            Some(_) => {
+                emote.fading_out = true;
+                wanted.remove(&emote.person);
+            }
+            None => emote.fading_out = true,
+        }
+    }
+
+    for (person, (need, position)) in wanted {
+        let index = EMOTE_NEEDS
+            .iter()
+            .position(|emote_need| *emote_need == need)
+            .unwrap_or_default();
+        commands.spawn((
+            SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index,
+                    color: Color::rgba(1.0, 1.0, 1.0, 0.0),
+                    ..default()
+                },
+                texture_atlas: asset_handles.emotes.clone(),
+                transform: Transform::from_translation(emote_position(position)),
+                ..default()
+            },
+            Emote {
+                person,
+                need,
+                fading_out: false,
+            },
+        ));
+    }
+}
+
+fn fade_emotes(
+    mut commands: Commands,
+    mut emotes_query: Query<(Entity, &Emote, &mut TextureAtlasSprite)>,
+    time: Res<Time>,
+) {
+    let step = time.delta_seconds() / EMOTE_FADE;
+    for (entity, emote, mut sprite) in &mut emotes_query {
+        let alpha = sprite.color.a();
+        match emote.fading_out {
+            true if alpha <= step => commands.entity(entity).despawn_recursive(),
+            true => {
+                sprite.color.set_a(alpha - step);
+            }
+            false if alpha < 1.0 => {
+                sprite.color.set_a((alpha + step).min(1.0));
+            }
+            false => {}
+        }
+    }
+}
+
+/// Keep emotes above the heads of their people, and drop those whose person is gone
+fn follow_persons(
+    mut commands: Commands,
+    mut emotes_query: Query<(Entity, &Emote, &mut Transform), Without<Person>>,
+    persons_query: Query<&Transform, With<Person>>,
+) {
+    for (entity, emote, mut transform) in &mut emotes_query {
+        match persons_query.get(emote.person) {
+            Ok(person_transform) => {
+                transform.translation = emote_position(person_transform.translation);
+            }
+            Err(_) => commands.entity(entity).despawn_recursive(),
+        }
+    }
+}
+
+/// Where an emote goes, above a person at the given position
+fn emote_position(person_position: Vec3) -> Vec3 {
+    let above = person_position.y + (SPRITE_SIZE.y + EMOTE_SIZE.y) / 2.0;
+    Vec3::new(person_position.x, above, EMOTE_LEVEL)
+}
+
+fn spawn_emote_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Emotes (N): ",
+                TextStyle {
+                    font_size: TEXT_SIZE - 6.0,
+                    ..default()
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font_size: TEXT_SIZE - 6.0,
+                color: Color::GOLD,
+                ..default()
+            }),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            // Under the overlay mode
+            right: Val::VMax(1.0),
+            top: Val::VMin(9.5),
+            ..default()
+        }),
+        EmoteText,
+    ));
+}
+
+fn update_emote_text(shown: Res<EmotesShown>, mut text_query: Query<&mut Text, With<EmoteText>>) {
+    text_query.single_mut().sections[1].value = match shown.0 {
+        true => "On",
+        false => "Off",
+    }
+    .to_string();
+}
+
+#[allow(clippy::type_complexity)]
+fn cleanup_emotes(
+    mut commands: Commands,
+    emotes_query: Query<Entity, Or<(With<Emote>, With<EmoteText>)>>,
+) {
+    for entity in &emotes_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,443 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    building::BuildingType,
+    debug::TEXT_SIZE,
+    mapgen::MapSettings,
+    notification::{Category, Notification},
+    person::{clamp_score, Person},
+    scenario::{start_scenario, ActiveScenario, Scenarios},
+    selection::clear_selection,
+    states::GameState,
+};
+
+/// The mean time between two random events, in seconds.
+const DEFAULT_EVENT_INTERVAL: f32 = 150.0;
+/// The health people lose each second during an epidemic.
+const EPIDEMIC_DRAIN: f32 = 1.5;
+/// The sport people lose each second during a heatwave.
+const HEATWAVE_DRAIN: f32 = 1.5;
+/// The social people gain each second near a Forum during a festival.
+const FESTIVAL_BOOST: f32 = 4.0;
+/// The distance to a Forum below which people enjoy a festival.
+const FESTIVAL_RADIUS: f32 = 96.0;
+
+/// Something happening to the whole city for a while.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    /// Health drains faster, Hospitals are needed
+    Epidemic,
+    /// Sport drains faster, Pools are needed
+    Heatwave,
+    /// People near Forums get more social
+    Festival,
+    /// A building stops working until the fire is out
+    Fire,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 4] = [
+        EventKind::Epidemic,
+        EventKind::Heatwave,
+        EventKind::Festival,
+        EventKind::Fire,
+    ];
+
+    /// The name used in scenario files
+    pub fn id(self) -> &'static str {
+        match self {
+            EventKind::Epidemic => "epidemic",
+            EventKind::Heatwave => "heatwave",
+            EventKind::Festival => "festival",
+            EventKind::Fire => "fire",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        EventKind::ALL.into_iter().find(|kind| kind.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Epidemic => "Epidemic",
+            EventKind::Heatwave => "Heatwave",
+            EventKind::Festival => "Festival",
+            EventKind::Fire => "Fire",
+        }
+    }
+
+    /// How long a random event of this kind lasts, in seconds
+    fn default_duration(self) -> f32 {
+        match self {
+            EventKind::Epidemic => 60.0,
+            EventKind::Heatwave => 45.0,
+            EventKind::Festival => 40.0,
+            EventKind::Fire => 30.0,
+        }
+    }
+
+    fn start_message(self) -> &'static str {
+        match self {
+            EventKind::Epidemic => "An epidemic broke out: people need Hospitals!",
+            EventKind::Heatwave => "A heatwave hits the city: people want to swim!",
+            EventKind::Festival => "A festival begins around the Forums!",
+            EventKind::Fire => "A fire broke out!",
+        }
+    }
+
+    fn end_message(self) -> &'static str {
+        match self {
+            EventKind::Epidemic => "The epidemic is over",
+            EventKind::Heatwave => "The heatwave is over",
+            EventKind::Festival => "The festival is over",
+            EventKind::Fire => "The fire is out",
+        }
+    }
+}
+
+/// An event due at some point of a game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledEvent {
+    pub kind: EventKind,
+    /// When it starts, in seconds since the game started
+    pub start: f32,
+    /// How long it lasts, in seconds
+    pub duration: f32,
+}
+
+/// How the events of a game are decided.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventSettings {
+    /// The seed and mean interval (in seconds) of random events, or `None` for none of them
+    pub random: Option<(u64, f32)>,
+    /// Events happening at set times
+    pub scripted: Vec<ScheduledEvent>,
+}
+
+impl Default for EventSettings {
+    fn default() -> Self {
+        EventSettings {
+            random: Some((1, DEFAULT_EVENT_INTERVAL)),
+            scripted: vec![],
+        }
+    }
+}
+
+/// An event under way.
+#[derive(Debug, Clone)]
+pub struct ActiveEvent {
+    pub kind: EventKind,
+    /// When it ends, in seconds since the game started
+    pub end: f32,
+    /// The building it happens to, for fires
+    pub target: Option<Entity>,
+}
+
+/// What happened, happens and will happen to the city, reset when a game starts.
+#[derive(Resource, Debug)]
+pub struct Timeline {
+    /// Seconds since the game started
+    pub elapsed: f32,
+    pub active: Vec<ActiveEvent>,
+    /// Scripted events still to come, soonest last
+    upcoming: Vec<ScheduledEvent>,
+    /// The next random event, if they are enabled
+    next_random: Option<ScheduledEvent>,
+    random_interval: f32,
+    rng: StdRng,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline::new(&EventSettings::default())
+    }
+}
+
+impl Timeline {
+    pub fn new(settings: &EventSettings) -> Self {
+        let (seed, interval) = settings.random.unwrap_or((0, 0.0));
+        let mut upcoming = settings.scripted.clone();
+        upcoming.sort_by(|a, b| b.start.total_cmp(&a.start));
+
+        let mut timeline = Timeline {
+            elapsed: 0.0,
+            active: vec![],
+            upcoming,
+            next_random: None,
+            random_interval: interval,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        if settings.random.is_some() {
+            timeline.schedule_random(0.0);
+        }
+        timeline
+    }
+
+    /// Draw the next random event, some time after the given one
+    fn schedule_random(&mut self, after: f32) {
+        let kind = *EventKind::ALL
+            .choose(&mut self.rng)
+            .unwrap_or(&EventKind::Festival);
+        let delay = self.random_interval * self.rng.gen_range(0.5..1.5);
+        self.next_random = Some(ScheduledEvent {
+            kind,
+            start: after + delay,
+            duration: kind.default_duration(),
+        });
+    }
+
+    /// The next event to come, if any
+    pub fn next(&self) -> Option<&ScheduledEvent> {
+        self.upcoming
+            .last()
+            .into_iter()
+            .chain(&self.next_random)
+            .min_by(|a, b| a.start.total_cmp(&b.start))
+    }
+
+    /// Take the events that are due
+    fn take_due(&mut self) -> Vec<ScheduledEvent> {
+        let mut due = vec![];
+        while self
+            .upcoming
+            .last()
+            .is_some_and(|event| event.start <= self.elapsed)
+        {
+            due.extend(self.upcoming.pop());
+        }
+        let random_due = self
+            .next_random
+            .as_ref()
+            .is_some_and(|event| event.start <= self.elapsed);
+        if let Some(event) = random_due.then(|| self.next_random.take()).flatten() {
+            self.schedule_random(event.start);
+            due.push(event);
+        }
+        due
+    }
+
+    pub fn is_active(&self, kind: EventKind) -> bool {
+        self.active.iter().any(|event| event.kind == kind)
+    }
+}
+
+/// Marks a building on fire: it does nothing until the fire is out.
+#[derive(Component, Debug)]
+pub struct Burning;
+
+#[derive(Component, Debug)]
+struct TimelineText;
+
+pub struct EventsPlugin;
+
+impl Plugin for EventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timeline>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (start_timeline.after(start_scenario), spawn_timeline_text),
+            )
+            .add_systems(
+                Update,
+                (run_timeline, apply_events, draw_fires, update_timeline_text)
+                    .chain()
+                    .after(clear_selection)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_timeline_text);
+    }
+}
+
+/// Plan the events of the active scenario, or random ones following the map seed in sandbox mode
+fn start_timeline(
+    mut timeline: ResMut<Timeline>,
+    scenarios: Res<Scenarios>,
+    active: Res<ActiveScenario>,
+    map_settings: Res<MapSettings>,
+) {
+    let settings = match active.0.and_then(|i| scenarios.0.get(i)) {
+        Some(scenario) => scenario.events.clone(),
+        None => EventSettings {
+            random: Some((map_settings.seed, DEFAULT_EVENT_INTERVAL)),
+            scripted: vec![],
+        },
+    };
+    *timeline = Timeline::new(&settings);
+}
+
+/// Start the events that are due and end those that are over
+fn run_timeline(
+    mut commands: Commands,
+    mut timeline: ResMut<Timeline>,
+    buildings_query: Query<(Entity, &BuildingType, &Transform), Without<Burning>>,
+    time: Res<Time>,
+    mut notifications: EventWriter<Notification>,
+) {
+    timeline.elapsed += time.delta_seconds();
+    let elapsed = timeline.elapsed;
+
+    let (over, ongoing): (Vec<ActiveEvent>, Vec<ActiveEvent>) = timeline
+        .active
+        .drain(..)
+        .partition(|event| event.end <= elapsed);
+    timeline.active = ongoing;
+    for event in over {
+        if let Some(target) = event.target {
+            if let Some(mut entity) = commands.get_entity(target) {
+                entity.remove::<Burning>();
+            }
+        }
+        notifications.send(Notification::new(Category::Event, event.kind.end_message()));
+    }
+
+    for event in timeline.take_due() {
+        let target = match event.kind {
+            EventKind::Fire => {
+                // Sorted by position, so that the same seed burns the same building.
+                // Entity numbers also depend on what was spawned just for looks
+                let mut candidates: Vec<(Entity, BuildingType, Vec2)> = buildings_query
+                    .iter()
+                    // Decorations don't burn
+                    .filter(|(_, b_type, _)| {
+                        !matches!(b_type, BuildingType::Tree | BuildingType::Lamp)
+                    })
+                    .map(|(entity, b_type, transform)| {
+                        (entity, *b_type, transform.translation.truncate())
+                    })
+                    .collect();
+                candidates
+                    .sort_by(|(_, _, a), (_, _, b)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+                let Some((target, b_type, _)) = candidates.choose(&mut timeline.rng).copied()
+                else {
+                    // Nothing to burn
+                    continue;
+                };
+                commands.entity(target).insert(Burning);
+                notifications.send(
+                    Notification::new(
+                        Category::Event,
+                        format!("A fire broke out in a {}!", b_type.name()),
+                    )
+                    .about(target),
+                );
+                Some(target)
+            }
+            _ => {
+                notifications.send(Notification::new(
+                    Category::Event,
+                    event.kind.start_message(),
+                ));
+                None
+            }
+        };
+        timeline.active.push(ActiveEvent {
+            kind: event.kind,
+            end: event.start + event.duration,
+            target,
+        });
+    }
+}
+
+/// What epidemics, heatwaves and festivals do to people
+pub fn apply_events(
+    timeline: Res<Timeline>,
+    mut persons_query: Query<(&mut Person, &Transform)>,
+    buildings_query: Query<(&BuildingType, &Transform), Without<Burning>>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    let epidemic = timeline.is_active(EventKind::Epidemic);
+    let heatwave = timeline.is_active(EventKind::Heatwave);
+    let forums: Vec<Vec3> = match timeline.is_active(EventKind::Festival) {
+        true => buildings_query
+            .iter()
+            .filter(|(b_type, _)| **b_type == BuildingType::Forum)
+            .map(|(_, transform)| transform.translation)
+            .collect(),
+        false => vec![],
+    };
+
+    for (mut person, transform) in &mut persons_query {
+        if epidemic {
+            person.health = clamp_score(person.health - EPIDEMIC_DRAIN * delta);
+        }
+        if heatwave {
+            person.sport = clamp_score(person.sport - HEATWAVE_DRAIN * delta);
+        }
+        let at_festival = forums
+            .iter()
+            .any(|forum| forum.distance(transform.translation) < FESTIVAL_RADIUS);
+        if at_festival {
+            person.social = clamp_score(person.social + FESTIVAL_BOOST * delta);
+        }
+    }
+}
+
+/// Flickering flames over burning buildings
+fn draw_fires(
+    burning_query: Query<&Transform, With<Burning>>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    let flicker = (time.elapsed_seconds() * 12.0).sin() * 0.5 + 0.5;
+    let color = Color::rgb(1.0, 0.3 + 0.4 * flicker, 0.0);
+    for transform in &burning_query {
+        let position = transform.translation.truncate();
+        for (offset, radius) in [(-6.0, 5.0), (0.0, 8.0 + 2.0 * flicker), (6.0, 5.0)] {
+            gizmos.circle_2d(position + Vec2::new(offset, radius), radius, color);
+        }
+    }
+}
+
+fn spawn_timeline_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: TEXT_SIZE - 8.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            // Under the objectives
+            right: Val::VMax(1.0),
+            top: Val::VMin(26.0),
+            ..default()
+        }),
+        TimelineText,
+    ));
+}
+
+/// List the events under way, and forecast the next one
+fn update_timeline_text(
+    timeline: Res<Timeline>,
+    mut text_query: Query<&mut Text, With<TimelineText>>,
+) {
+    let mut lines: Vec<String> = timeline
+        .active
+        .iter()
+        .map(|event| {
+            format!(
+                "{}: {:.0}s left",
+                event.kind.name(),
+                event.end - timeline.elapsed
+            )
+        })
+        .collect();
+    if let Some(event) = timeline.next() {
+        lines.push(format!(
+            "Next: {} in {:.0}s",
+            event.kind.name(),
+            event.start - timeline.elapsed
+        ));
+    }
+    text_query.single_mut().sections[0].value = lines.join("\n");
+}
+
+fn cleanup_timeline_text(mut commands: Commands, text_query: Query<Entity, With<TimelineText>>) {
+    for entity in &text_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,54 +1,363 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
 use std::ops::Range;
 
-use crate::asset_loader::AssetHandles;
+use crate::{asset_loader::AssetHandles, building::BuildingType};
 
-const TILE_SCALE: Vec3 = Vec3::new(1.0, 1.0, 0.0);
-const TILE_SIZE: Vec3 = Vec3::new(16.0, 16.0, 0.0);
-const TILES_RANGE_X: Range<i32> = -120..120;
-const TILES_RANGE_Y: Range<i32> = -100..100;
+pub const TILE_SIZE: Vec3 = Vec3::new(16.0, 16.0, 0.0);
+pub const TILES_RANGE_X: Range<i32> = -120..120;
+pub const TILES_RANGE_Y: Range<i32> = -100..100;
+/// The number of tiles on each side of a ground chunk.
+const CHUNK_SIZE: i32 = 40;
 
-#[derive(Component, Debug)]
-struct GroundTile;
+/// The map the city is built on, when nothing else is chosen.
+pub const DEFAULT_MAP: &str = include_str!("../assets/maps/default.map");
+
+/// What a tile is made of.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Terrain {
+    #[default]
+    Road,
+    Grass,
+    Water,
+    Blocked,
+}
+
+impl Terrain {
+    /// The colour the tile texture is multiplied by
+    fn tint(self) -> Color {
+        match self {
+            Terrain::Road => Color::WHITE,
+            Terrain::Grass => Color::rgb(0.55, 0.85, 0.45),
+            Terrain::Water => Color::rgb(0.35, 0.55, 1.0),
+            Terrain::Blocked => Color::rgb(0.35, 0.3, 0.3),
+        }
+    }
+
+    /// How fast people walk on this terrain, relative to grass
+    pub fn speed(self) -> f32 {
+        match self {
+            Terrain::Road => 1.5,
+            Terrain::Grass => 1.0,
+            Terrain::Water | Terrain::Blocked => 0.5,
+        }
+    }
+
+    fn buildable(self) -> bool {
+        matches!(self, Terrain::Road | Terrain::Grass)
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "road" => Some(Terrain::Road),
+            "grass" => Some(Terrain::Grass),
+            "water" => Some(Terrain::Water),
+            "blocked" => Some(Terrain::Blocked),
+            _ => None,
+        }
+    }
+}
+
+/// What a tile is reserved for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    Residential,
+    Commercial,
+    Leisure,
+}
+
+impl Zone {
+    /// The colour multiplied with the terrain's tint
+    fn tint(self) -> [f32; 3] {
+        match self {
+            Zone::Residential => [1.0, 0.95, 0.7],
+            Zone::Commercial => [0.8, 0.85, 1.0],
+            Zone::Leisure => [1.0, 0.8, 0.9],
+        }
+    }
 
-#[derive(Bundle)]
-struct TileBundle {
-    tile: GroundTile,
-    sprite: SpriteBundle,
+    /// Returns `true` if the building type may be placed on this zone
+    fn allows(self, b_type: BuildingType) -> bool {
+        match b_type {
+            // Decorations go anywhere
+            BuildingType::Tree | BuildingType::Lamp => true,
+            BuildingType::House => self == Zone::Residential,
+            BuildingType::Hospital => self == Zone::Residential || self == Zone::Commercial,
+            BuildingType::Restaurant | BuildingType::Creative => self == Zone::Commercial,
+            BuildingType::Cinema => self == Zone::Commercial || self == Zone::Leisure,
+            BuildingType::Forum | BuildingType::Pool => self == Zone::Leisure,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "residential" => Some(Zone::Residential),
+            "commercial" => Some(Zone::Commercial),
+            "leisure" => Some(Zone::Leisure),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct GroundTile {
+    pub terrain: Terrain,
+    pub zone: Option<Zone>,
+}
+
+impl GroundTile {
+    fn tint(&self) -> Color {
+        let tint = self.terrain.tint();
+        match self.zone {
+            Some(zone) => tint * zone.tint(),
+            None => tint,
+        }
+    }
+}
+
+/// All the tiles of the map, row by row from the bottom left.
+#[derive(Resource, Debug)]
+pub struct Ground {
+    tiles: Vec<GroundTile>,
+    /// Tile coordinates where new people arrive in the city
+    pub entry_points: Vec<IVec2>,
+}
+
+impl Default for Ground {
+    fn default() -> Self {
+        Ground {
+            tiles: vec![GroundTile::default(); TILES_RANGE_X.len() * TILES_RANGE_Y.len()],
+            entry_points: vec![],
+        }
+    }
+}
+
+impl Ground {
+    /// Build the ground from a map file.
+    ///
+    /// Each line paints a rectangle of tiles, bounds included:
+    /// `terrain <road|grass|water|blocked> <x_min> <y_min> <x_max> <y_max>` or
+    /// `zone <residential|commercial|leisure|none> <x_min> <y_min> <x_max> <y_max>`.
+    /// Later lines paint over earlier ones, and `#` starts a comment.
+    /// `entry <x> <y>` adds a tile where new people arrive.
+    pub fn from_map(map: &str) -> Result<Self, String> {
+        let mut ground = Ground::default();
+
+        for (line_nb, line) in map.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = || format!("Invalid map line {}: {}", line_nb + 1, line);
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["entry", x_pos, y_pos] = words[..] {
+                let x_pos = x_pos.parse().map_err(|_| error())?;
+                let y_pos = y_pos.parse().map_err(|_| error())?;
+                ground.entry_points.push(IVec2::new(x_pos, y_pos));
+                continue;
+            }
+            let [layer, kind, x_min, y_min, x_max, y_max] = words[..] else {
+                return Err(error());
+            };
+            let bounds: Vec<i32> = [x_min, y_min, x_max, y_max]
+                .iter()
+                .map(|word| word.parse().map_err(|_| error()))
+                .collect::<Result<_, _>>()?;
+            let bottom_left = IVec2::new(bounds[0], bounds[1]);
+            let top_right = IVec2::new(bounds[2], bounds[3]);
+
+            match layer {
+                "terrain" => {
+                    let terrain = Terrain::parse(kind).ok_or_else(error)?;
+                    ground.fill(bottom_left, top_right, |tile| tile.terrain = terrain);
+                }
+                "zone" => {
+                    let zone = match kind {
+                        "none" => None,
+                        _ => Some(Zone::parse(kind).ok_or_else(error)?),
+                    };
+                    ground.fill(bottom_left, top_right, |tile| tile.zone = zone);
+                }
+                _ => return Err(error()),
+            }
+        }
+        Ok(ground)
+    }
+
+    /// The tile at the given tile coordinates, if inside the map
+    pub fn get(&self, x_pos: i32, y_pos: i32) -> Option<&GroundTile> {
+        Self::index(x_pos, y_pos).map(|i| &self.tiles[i])
+    }
+
+    /// Change every tile of a rectangle, bounds included. Tiles outside the map are ignored
+    pub fn fill(
+        &mut self,
+        bottom_left: IVec2,
+        top_right: IVec2,
+        mut paint: impl FnMut(&mut GroundTile),
+    ) {
+        for x_pos in bottom_left.x..=top_right.x {
+            for y_pos in bottom_left.y..=top_right.y {
+                if let Some(i) = Self::index(x_pos, y_pos) {
+                    paint(&mut self.tiles[i]);
+                }
+            }
+        }
+    }
+
+    /// The tile under the given world position, if inside the map
+    pub fn tile_at(&self, world_pos: Vec2) -> Option<&GroundTile> {
+        let tile_pos = to_tile_pos(world_pos);
+        self.get(tile_pos.x, tile_pos.y)
+    }
+
+    /// Returns `true` if a building of the given type and size can stand
+    /// centered on the given world position: every tile under it must be
+    /// buildable, and the zone under its center must allow it
+    pub fn can_build(&self, b_type: BuildingType, center: Vec2, size: Vec2) -> bool {
+        let bottom_left = to_tile_pos(center - size / 2.0);
+        let top_right = to_tile_pos(center + size / 2.0);
+        for x_pos in bottom_left.x..=top_right.x {
+            for y_pos in bottom_left.y..=top_right.y {
+                match self.get(x_pos, y_pos) {
+                    Some(tile) if tile.terrain.buildable() => {}
+                    _ => return false,
+                }
+            }
+        }
+
+        match self.tile_at(center).and_then(|tile| tile.zone) {
+            Some(zone) => zone.allows(b_type),
+            None => true,
+        }
+    }
+
+    fn index(x_pos: i32, y_pos: i32) -> Option<usize> {
+        if !TILES_RANGE_X.contains(&x_pos) || !TILES_RANGE_Y.contains(&y_pos) {
+            return None;
+        }
+        let column = (x_pos - TILES_RANGE_X.start) as usize;
+        let row = (y_pos - TILES_RANGE_Y.start) as usize;
+        Some(row * TILES_RANGE_X.len() + column)
+    }
+}
+
+/// A square of tiles drawn as a single mesh.
+#[derive(Component, Debug)]
+struct GroundChunk {
+    /// Tile coordinates of the bottom left tile
+    origin: IVec2,
 }
 
 pub struct GroundPlugin;
 
 impl Plugin for GroundPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_floor);
+        app.insert_resource(Ground::from_map(DEFAULT_MAP).expect("Invalid default map"))
+            .add_systems(Startup, spawn_floor)
+            .add_systems(Update, redraw_chunks);
     }
 }
 
-/// Spawns all floor tiles in a grid
-fn spawn_floor(mut commands: Commands, asset_handles: Res<AssetHandles>) {
-    for x_pos in TILES_RANGE_X {
-        for y_pos in TILES_RANGE_Y {
-            spawn_tile(x_pos, y_pos, &mut commands, &asset_handles);
+/// Spawns one mesh per chunk of tiles, instead of one sprite per tile
+fn spawn_floor(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    ground: Res<Ground>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let material = materials.add(ColorMaterial {
+        texture: Some(asset_handles.street.clone()),
+        ..default()
+    });
+
+    for x_origin in TILES_RANGE_X.step_by(CHUNK_SIZE as usize) {
+        for y_origin in TILES_RANGE_Y.step_by(CHUNK_SIZE as usize) {
+            let chunk = GroundChunk {
+                origin: IVec2::new(x_origin, y_origin),
+            };
+            commands.spawn((
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(build_chunk_mesh(&chunk, &ground)).into(),
+                    material: material.clone(),
+                    ..default()
+                },
+                chunk,
+            ));
         }
     }
 }
 
-fn spawn_tile(x_pos: i32, y_pos: i32, commands: &mut Commands, asset_handles: &Res<AssetHandles>) {
-    commands.spawn(TileBundle {
-        tile: GroundTile,
-        sprite: SpriteBundle {
-            texture: asset_handles.street.clone(),
-            transform: Transform {
-                translation: Vec3::new(
-                    x_pos as f32 * TILE_SIZE.x * TILE_SCALE.x,
-                    y_pos as f32 * TILE_SIZE.y * TILE_SCALE.y,
-                    0.0,
-                ),
-                scale: TILE_SCALE,
-                ..default()
-            },
-            ..default()
-        },
-    });
+/// Rebuild the chunk meshes whenever a tile changes
+fn redraw_chunks(
+    ground: Res<Ground>,
+    chunks_query: Query<(&GroundChunk, &Mesh2dHandle)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !ground.is_changed() || ground.is_added() {
+        return;
+    }
+    for (chunk, handle) in &chunks_query {
+        if let Some(mesh) = meshes.get_mut(&handle.0) {
+            *mesh = build_chunk_mesh(chunk, &ground);
+        }
+    }
+}
+
+/// One textured quad per tile, tinted by the tile's terrain and zone
+fn build_chunk_mesh(chunk: &GroundChunk, ground: &Ground) -> Mesh {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+
+    for x_pos in chunk.origin.x..(chunk.origin.x + CHUNK_SIZE).min(TILES_RANGE_X.end) {
+        for y_pos in chunk.origin.y..(chunk.origin.y + CHUNK_SIZE).min(TILES_RANGE_Y.end) {
+            let Some(tile) = ground.get(x_pos, y_pos) else {
+                continue;
+            };
+            let center = Vec2::new(x_pos as f32 * TILE_SIZE.x, y_pos as f32 * TILE_SIZE.y);
+            let half = TILE_SIZE.truncate() / 2.0;
+            let first_vertex = positions.len() as u32;
+
+            positions.extend([
+                [center.x - half.x, center.y - half.y, 0.0],
+                [center.x + half.x, center.y - half.y, 0.0],
+                [center.x + half.x, center.y + half.y, 0.0],
+                [center.x - half.x, center.y + half.y, 0.0],
+            ]);
+            uvs.extend([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+            colors.extend([tile.tint().as_linear_rgba_f32(); 4]);
+            indices.extend([
+                first_vertex,
+                first_vertex + 1,
+                first_vertex + 2,
+                first_vertex,
+                first_vertex + 2,
+                first_vertex + 3,
+            ]);
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// The coordinates of the tile under the given world position
+pub fn to_tile_pos(world_pos: Vec2) -> IVec2 {
+    (world_pos / TILE_SIZE.truncate()).round().as_ivec2()
+}
+
+/// The world position of the center of the given tile
+pub fn to_world_pos(tile_pos: IVec2) -> Vec2 {
+    tile_pos.as_vec2() * TILE_SIZE.truncate()
 }
@@ -0,0 +1,406 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    asset_loader::AssetHandles,
+    building::{
+        spawn_building, trigger_spawn, BuildingCooldowns, BuildingType, UnlockedBuildings,
+        STOCK_LIMIT,
+    },
+    drag::Dropped,
+    economy::{placement_cost, Paid, Treasury},
+    notification::{Category, Notification},
+    states::GameState,
+};
+
+/// The maximum number of actions that can be undone.
+const HISTORY_SIZE: usize = 50;
+
+/// Something the player did to a building, with what it takes to undo it.
+#[derive(Debug, Clone)]
+pub enum Action {
+    Placed {
+        entity: Entity,
+        b_type: BuildingType,
+        transform: Transform,
+    },
+    Moved {
+        entity: Entity,
+        from: Vec2,
+        to: Vec2,
+    },
+    Rotated {
+        entity: Entity,
+        from: Quat,
+        to: Quat,
+    },
+    Demolished {
+        entity: Entity,
+        b_type: BuildingType,
+        transform: Transform,
+        paid: Option<Paid>,
+        refund: f32,
+    },
+    /// Actions done at once, like moving a selection, undone at once
+    Group(Vec<Action>),
+}
+
+impl Action {
+    /// A building got respawned by undoing or redoing: follow it in this action
+    fn replace_entity(&mut self, old: Entity, new: Entity) {
+        match self {
+            Action::Placed { entity, .. }
+            | Action::Moved { entity, .. }
+            | Action::Rotated { entity, .. }
+            | Action::Demolished { entity, .. } => {
+                if *entity == old {
+                    *entity = new;
+                }
+            }
+            Action::Group(actions) => {
+                for action in actions {
+                    action.replace_entity(old, new);
+                }
+            }
+        }
+    }
+
+    /// What doing the action again costs
+    fn cost(&self) -> f32 {
+        match self {
+            Action::Placed { b_type, .. } => placement_cost(*b_type),
+            Action::Group(actions) => actions.iter().map(Action::cost).sum(),
+            _ => 0.0,
+        }
+    }
+
+    /// Count the buildings doing the action again spawns, by type
+    fn count_placed(&self, counts: &mut HashMap<BuildingType, u32>) {
+        match self {
+            Action::Placed { b_type, .. } => *counts.entry(*b_type).or_default() += 1,
+            Action::Group(actions) => {
+                for action in actions {
+                    action.count_placed(counts);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// What the action gave back to the city
+    pub fn refund(&self) -> f32 {
+        match self {
+            Action::Demolished { refund, .. } => *refund,
+            Action::Group(actions) => actions.iter().map(Action::refund).sum(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Sent by whatever lets the player change buildings, to make it undoable.
+#[derive(Event, Debug, Clone)]
+pub struct ActionDone(pub Action);
+
+/// Actions that can be undone, the latest last, and those that can be redone.
+#[derive(Resource, Debug, Default)]
+pub struct History {
+    undo: VecDeque<Action>,
+    redo: Vec<Action>,
+}
+
+impl History {
+    fn push(&mut self, action: Action) {
+        if self.undo.len() >= HISTORY_SIZE {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(action);
+    }
+
+    fn replace_entities(&mut self, respawned: &[(Entity, Entity)]) {
+        for action in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for (old, new) in respawned {
+                action.replace_entity(*old, *new);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .add_event::<ActionDone>()
+            .add_systems(OnEnter(GameState::Playing), clear_history)
+            .add_systems(
+                Update,
+                (record_actions, step_history)
+                    .chain()
+                    .before(trigger_spawn)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn clear_history(mut history: ResMut<History>) {
+    history.clear();
+}
+
+/// Add new actions to the history, which makes the undone ones impossible
+/// to redo. Actions done in the same frame are undone together
+fn record_actions(
+    mut history: ResMut<History>,
+    mut actions: EventReader<ActionDone>,
+    mut dropped: EventReader<Dropped>,
+    buildings_query: Query<(), With<BuildingType>>,
+) {
+    let moves = dropped
+        .read()
+        .filter(|drop| buildings_query.contains(drop.entity))
+        .map(|drop| Action::Moved {
+            entity: drop.entity,
+            from: drop.from,
+            to: drop.to,
+        });
+    let mut new_actions: Vec<Action> = actions
+        .read()
+        .map(|done| done.0.clone())
+        .chain(moves)
+        .collect();
+
+    let action = match new_actions.len() {
+        0 => return,
+        1 => new_actions.remove(0),
+        _ => Action::Group(new_actions),
+    };
+    history.redo.clear();
+    history.push(action);
+}
+
+/// Ctrl+Z undoes the last action, Ctrl+Y (or Ctrl+Shift+Z) redoes it.
+/// Buildings placed again come out of the stock, as when first placed
+#[allow(clippy::too_many_arguments)]
+pub fn step_history(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    keys: Res<Input<KeyCode>>,
+    mut history: ResMut<History>,
+    mut treasury: ResMut<Treasury>,
+    mut cooldowns: ResMut<BuildingCooldowns>,
+    unlocked: Res<UnlockedBuildings>,
+    mut notifications: EventWriter<Notification>,
+    mut buildings_query: Query<&mut Transform, With<BuildingType>>,
+) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let redo = keys.just_pressed(KeyCode::Y) || (shift && keys.just_pressed(KeyCode::Z));
+    let undo = !redo && keys.just_pressed(KeyCode::Z);
+    let mut respawned = vec![];
+
+    if undo {
+        let Some(mut action) = history.undo.pop_back() else {
+            return;
+        };
+        revert(
+            &action,
+            &mut commands,
+            &asset_handles,
+            &mut treasury,
+            &mut cooldowns,
+            &mut buildings_query,
+            &mut respawned,
+        );
+        history.replace_entities(&respawned);
+        for (old, new) in &respawned {
+            action.replace_entity(*old, *new);
+        }
+        history.redo.push(action);
+    } else if redo {
+        let Some(mut action) = history.redo.pop() else {
+            return;
+        };
+        let cost = action.cost();
+        let mut counts = HashMap::new();
+        action.count_placed(&mut counts);
+        let missing_stock = counts.iter().find(|(b_type, count)| {
+            cooldowns
+                .0
+                .get(*b_type)
+                .is_some_and(|cooldown| cooldown.stock < **count)
+        });
+        let refusal =
+            if let Some(b_type) = counts.keys().find(|b_type| !unlocked.0.contains(*b_type)) {
+                Some(format!("{} is locked", b_type.name()))
+            } else if let Some((b_type, count)) = missing_stock {
+                Some(format!(
+                    "Needs {} {} in stock to redo this",
+                    count,
+                    b_type.name()
+                ))
+            } else if treasury.balance < cost {
+                Some(format!("Not enough funds to redo this (${:.0})", cost))
+            } else {
+                None
+            };
+        if let Some(message) = refusal {
+            notifications.send(Notification::new(Category::Building, message));
+            history.redo.push(action);
+            return;
+        }
+        treasury.balance -= cost;
+        reapply(
+            &action,
+            &mut commands,
+            &asset_handles,
+            &mut treasury,
+            &mut cooldowns,
+            &mut buildings_query,
+            &mut respawned,
+        );
+        history.replace_entities(&respawned);
+        for (old, new) in &respawned {
+            action.replace_entity(*old, *new);
+        }
+        history.push(action);
+    }
+}
+
+/// Put things back as they were before the action, noting
+/// the buildings spawned back in place of the old ones
+fn revert(
+    action: &Action,
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+    treasury: &mut Treasury,
+    cooldowns: &mut BuildingCooldowns,
+    buildings_query: &mut Query<&mut Transform, With<BuildingType>>,
+    respawned: &mut Vec<(Entity, Entity)>,
+) {
+    match action {
+        Action::Placed { entity, b_type, .. } => {
+            commands.entity(*entity).despawn_recursive();
+            treasury.balance += placement_cost(*b_type);
+            if let Some(cooldown) = cooldowns.0.get_mut(b_type) {
+                cooldown.stock = (cooldown.stock + 1).min(STOCK_LIMIT);
+            }
+        }
+        Action::Moved { entity, from, .. } => {
+            if let Ok(mut transform) = buildings_query.get_mut(*entity) {
+                transform.translation.x = from.x;
+                transform.translation.y = from.y;
+            }
+        }
+        Action::Rotated { entity, from, .. } => {
+            if let Ok(mut transform) = buildings_query.get_mut(*entity) {
+                transform.rotation = *from;
+            }
+        }
+        Action::Demolished {
+            entity,
+            b_type,
+            transform,
+            paid,
+            refund,
+        } => {
+            treasury.balance -= refund;
+            let new = respawn(*b_type, *transform, commands, asset_handles);
+            if let Some(paid) = paid {
+                commands.entity(new).insert(*paid);
+            }
+            respawned.push((*entity, new));
+        }
+        Action::Group(actions) => {
+            for action in actions.iter().rev() {
+                revert(
+                    action,
+                    commands,
+                    asset_handles,
+                    treasury,
+                    cooldowns,
+                    buildings_query,
+                    respawned,
+                );
+            }
+        }
+    }
+}
+
+/// Do the action again, once it has been undone, paid for and checked
+/// for stock, noting the buildings spawned back in place of the old ones
+fn reapply(
+    action: &Action,
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+    treasury: &mut Treasury,
+    cooldowns: &mut BuildingCooldowns,
+    buildings_query: &mut Query<&mut Transform, With<BuildingType>>,
+    respawned: &mut Vec<(Entity, Entity)>,
+) {
+    match action {
+        Action::Placed {
+            entity,
+            b_type,
+            transform,
+        } => {
+            let new = respawn(*b_type, *transform, commands, asset_handles);
+            commands.entity(new).insert(Paid(placement_cost(*b_type)));
+            if let Some(cooldown) = cooldowns.0.get_mut(b_type) {
+                cooldown.stock -= 1;
+            }
+            respawned.push((*entity, new));
+        }
+        Action::Moved { entity, to, .. } => {
+            if let Ok(mut transform) = buildings_query.get_mut(*entity) {
+                transform.translation.x = to.x;
+                transform.translation.y = to.y;
+            }
+        }
+        Action::Rotated { entity, to, .. } => {
+            if let Ok(mut transform) = buildings_query.get_mut(*entity) {
+                transform.rotation = *to;
+            }
+        }
+        Action::Demolished { entity, refund, .. } => {
+            commands.entity(*entity).despawn_recursive();
+            treasury.balance += refund;
+        }
+        Action::Group(actions) => {
+            for action in actions {
+                reapply(
+                    action,
+                    commands,
+                    asset_handles,
+                    treasury,
+                    cooldowns,
+                    buildings_query,
+                    respawned,
+                );
+            }
+        }
+    }
+}
+
+/// Spawn a building back, with the position and rotation it had
+fn respawn(
+    b_type: BuildingType,
+    transform: Transform,
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+) -> Entity {
+    let entity = spawn_building(
+        b_type,
+        transform.translation.truncate(),
+        commands,
+        asset_handles,
+    );
+    commands.entity(entity).insert(transform);
+    entity
+}
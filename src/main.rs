@@ -1,35 +1,94 @@
 // disable console on windows for release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod animation;
 mod asset_loader;
+mod balance;
+mod beauty;
+mod blueprint;
 mod building;
 mod camera;
+mod daynight;
 mod debug;
+mod depth;
 mod drag;
+mod economy;
+mod emote;
+mod events;
 mod ground;
+mod history;
+mod mapgen;
 mod menu;
+mod minimap;
 mod movement;
+mod notification;
+mod overlay;
+mod palette;
 mod person;
+mod replay;
+mod scenario;
 mod score;
+mod selection;
 mod selector;
 mod states;
+mod stats;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod tools;
+mod unlock;
 
+use animation::AnimationPlugin;
 use asset_loader::AssetLoaderPlugin;
+use balance::{run_balance, BalanceSettings};
 use bevy::{asset::AssetMetaCheck, prelude::*};
+use blueprint::BlueprintPlugin;
 use building::BuildingPlugin;
 use camera::Camera2dPlugin;
+use daynight::DayNightPlugin;
 // use debug::DebugPlugin;
+use depth::DepthPlugin;
 use drag::DragPlugin;
+use economy::EconomyPlugin;
+use emote::EmotePlugin;
+use events::EventsPlugin;
 use ground::GroundPlugin;
+use history::HistoryPlugin;
+use mapgen::MapGenPlugin;
 use menu::MenuPlugin;
+use minimap::MinimapPlugin;
 use movement::MovementPlugin;
+use notification::NotificationPlugin;
+use overlay::OverlayPlugin;
+use palette::PalettePlugin;
 use person::PersonPlugin;
+use replay::ReplayPlugin;
+use scenario::ScenarioPlugin;
 use score::ScorePlugin;
+use selection::SelectionPlugin;
 use selector::SelectorPlugin;
 use states::GameState;
+use stats::StatsPlugin;
+use telemetry::TelemetryPlugin;
+use tools::ToolsPlugin;
+use unlock::UnlockPlugin;
 
 fn main() {
-    App::new()
+    let args: Vec<String> = std::env::args().collect();
+    match BalanceSettings::from_args(&args) {
+        Some(Ok(settings)) => {
+            run_balance(&settings, add_game);
+            return;
+        }
+        Some(Err(error)) => {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+        None => {}
+    }
+
+    let mut app = App::new();
+    app
         // Built-ins
         .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.3)))
         // No anti-aliasing for pixel art
@@ -46,21 +105,44 @@ fn main() {
                 ..default()
             }),
             ..default()
-        }))
-        // Custom
-        .add_state::<GameState>()
+        }));
+    add_game(&mut app);
+    app.run();
+}
+
+/// Add the game to an app that has Bevy's plugins, with or without a window
+fn add_game(app: &mut App) {
+    app.add_state::<GameState>()
         .add_plugins((
             AssetLoaderPlugin,
             BuildingPlugin,
             Camera2dPlugin,
             DragPlugin,
             GroundPlugin,
+            MapGenPlugin,
             MenuPlugin,
+            MinimapPlugin,
             MovementPlugin,
+            OverlayPlugin,
             PersonPlugin,
+            ScenarioPlugin,
             ScorePlugin,
             SelectorPlugin,
         ))
-        // .add_plugins(DebugPlugin)
-        .run();
+        .add_plugins((
+            AnimationPlugin,
+            BlueprintPlugin,
+            DayNightPlugin,
+            DepthPlugin,
+            EconomyPlugin,
+            EmotePlugin,
+            EventsPlugin,
+            HistoryPlugin,
+            NotificationPlugin,
+            PalettePlugin,
+            ReplayPlugin,
+            SelectionPlugin,
+        ))
+        .add_plugins((StatsPlugin, TelemetryPlugin, ToolsPlugin, UnlockPlugin));
+    // app.add_plugins(DebugPlugin);
 }
@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    asset_loader::AssetHandles,
+    building::{get_size_from_type, spawn_building, BuildingType},
+    ground::{to_world_pos, Ground, Terrain, Zone, DEFAULT_MAP, TILES_RANGE_X, TILES_RANGE_Y},
+    states::GameState,
+};
+
+/// Half the side of the paved city center, in tiles.
+const CITY_RADIUS: i32 = 30;
+/// Half the side of the square around the origin kept free of zones and parks,
+/// so that new buildings can always appear there.
+const SPAWN_RADIUS: i32 = 8;
+const RIVER_CHANCE: f64 = 0.8;
+/// The minimum distance between two starter buildings, in world units.
+const STARTER_SPACING: f32 = 80.0;
+/// How many random spots are tried for each starter building.
+const STARTER_TRIES: usize = 50;
+const STARTER_BUILDINGS: [BuildingType; 4] = [
+    BuildingType::House,
+    BuildingType::House,
+    BuildingType::Restaurant,
+    BuildingType::Forum,
+];
+
+/// Where the map comes from.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MapSource {
+    #[default]
+    Handmade,
+    Generated,
+}
+
+/// The map chosen in the main menu.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct MapSettings {
+    pub source: MapSource,
+    pub seed: u64,
+    pub starter_buildings: bool,
+}
+
+impl Default for MapSettings {
+    fn default() -> Self {
+        MapSettings {
+            source: MapSource::default(),
+            seed: 1,
+            starter_buildings: true,
+        }
+    }
+}
+
+pub struct MapGenPlugin;
+
+impl Plugin for MapGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapSettings>()
+            .add_systems(OnEnter(GameState::Playing), build_map);
+    }
+}
+
+/// Lay out the ground the game is played on, as chosen in the menu
+pub fn build_map(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    settings: Res<MapSettings>,
+    mut ground: ResMut<Ground>,
+) {
+    match settings.source {
+        MapSource::Handmade => {
+            *ground = Ground::from_map(DEFAULT_MAP).expect("Invalid default map");
+        }
+        MapSource::Generated => {
+            let mut rng = StdRng::seed_from_u64(settings.seed);
+            *ground = generate_ground(&mut rng);
+            if settings.starter_buildings {
+                place_starter_buildings(&mut rng, &ground, &mut commands, &asset_handles);
+            }
+        }
+    }
+}
+
+/// Generate a city center crossed by avenues, with parks, hills, zones
+/// and maybe a river, all decided by the given random generator
+fn generate_ground(rng: &mut StdRng) -> Ground {
+    let mut ground = Ground::default();
+    let map_min = IVec2::new(TILES_RANGE_X.start, TILES_RANGE_Y.start);
+    let map_max = IVec2::new(TILES_RANGE_X.end - 1, TILES_RANGE_Y.end - 1);
+
+    ground.fill(map_min, map_max, |tile| tile.terrain = Terrain::Grass);
+    ground.fill(
+        IVec2::splat(-CITY_RADIUS),
+        IVec2::splat(CITY_RADIUS),
+        |tile| tile.terrain = Terrain::Road,
+    );
+
+    // Hills, out of the city
+    for _ in 0..rng.gen_range(1..4) {
+        let center = random_outskirt(rng, map_max);
+        let half_size = IVec2::new(rng.gen_range(3..10), rng.gen_range(3..10));
+        ground.fill(center - half_size, center + half_size, |tile| {
+            tile.terrain = Terrain::Blocked
+        });
+    }
+
+    // Parks, in the city
+    for _ in 0..rng.gen_range(2..6) {
+        let size = IVec2::new(rng.gen_range(4..10), rng.gen_range(4..10));
+        let corner = random_away_from_spawn(rng, CITY_RADIUS - 10, size);
+        ground.fill(corner, corner + size, |tile| tile.terrain = Terrain::Grass);
+    }
+
+    // A meandering river, drawn before the roads so that they bridge over it
+    if rng.gen_bool(RIVER_CHANCE) {
+        let width = rng.gen_range(3..7);
+        let vertical = rng.gen_bool(0.5);
+        let (along, across_max) = match vertical {
+            true => (TILES_RANGE_Y, map_max.x - width),
+            false => (TILES_RANGE_X, map_max.y - width),
+        };
+        // Keep the river on one side of the city center as it meanders
+        let (across_min, across_max) = match rng.gen_bool(0.5) {
+            true => (CITY_RADIUS + 5, across_max),
+            false => (-across_max, -CITY_RADIUS - 5 - width),
+        };
+        let mut offset = rng.gen_range(across_min..across_max);
+        for position in along {
+            offset = (offset + rng.gen_range(-1..=1)).clamp(across_min, across_max);
+            let (start, end) = match vertical {
+                true => (
+                    IVec2::new(offset, position),
+                    IVec2::new(offset + width, position),
+                ),
+                false => (
+                    IVec2::new(position, offset),
+                    IVec2::new(position, offset + width),
+                ),
+            };
+            ground.fill(start, end, |tile| tile.terrain = Terrain::Water);
+        }
+    }
+
+    // Avenues crossing the whole map, and a few more streets
+    let crossing = IVec2::new(rng.gen_range(-20..=20), rng.gen_range(-20..=20));
+    ground.fill(
+        IVec2::new(map_min.x, crossing.y - 1),
+        IVec2::new(map_max.x, crossing.y + 2),
+        |tile| tile.terrain = Terrain::Road,
+    );
+    ground.fill(
+        IVec2::new(crossing.x - 1, map_min.y),
+        IVec2::new(crossing.x + 2, map_max.y),
+        |tile| tile.terrain = Terrain::Road,
+    );
+    for _ in 0..rng.gen_range(2..6) {
+        let (start, end) = match rng.gen_bool(0.5) {
+            true => {
+                let y_pos = rng.gen_range(map_min.y..map_max.y);
+                (
+                    IVec2::new(map_min.x, y_pos),
+                    IVec2::new(map_max.x, y_pos + 1),
+                )
+            }
+            false => {
+                let x_pos = rng.gen_range(map_min.x..map_max.x);
+                (
+                    IVec2::new(x_pos, map_min.y),
+                    IVec2::new(x_pos + 1, map_max.y),
+                )
+            }
+        };
+        ground.fill(start, end, |tile| tile.terrain = Terrain::Road);
+    }
+
+    // Zones around the city center
+    for _ in 0..rng.gen_range(3..7) {
+        let zone = *[Zone::Residential, Zone::Commercial, Zone::Leisure]
+            .choose(rng)
+            .unwrap_or(&Zone::Residential);
+        let size = IVec2::new(rng.gen_range(10..25), rng.gen_range(10..25));
+        let corner = random_away_from_spawn(rng, CITY_RADIUS, size);
+        ground.fill(corner, corner + size, |tile| tile.zone = Some(zone));
+    }
+
+    // People come in through the avenues, where they leave the city center
+    let mut gates = vec![
+        IVec2::new(-CITY_RADIUS, crossing.y),
+        IVec2::new(CITY_RADIUS, crossing.y),
+        IVec2::new(crossing.x, -CITY_RADIUS),
+        IVec2::new(crossing.x, CITY_RADIUS),
+    ];
+    gates.shuffle(rng);
+    gates.truncate(rng.gen_range(1..=4));
+    ground.entry_points = gates;
+
+    ground
+}
+
+/// A random tile out of the city center, but still inside the map
+fn random_outskirt(rng: &mut StdRng, map_max: IVec2) -> IVec2 {
+    let side = *[-1, 1].choose(rng).unwrap_or(&1);
+    let far = rng.gen_range(CITY_RADIUS + 10..map_max.y);
+    let along = rng.gen_range(-map_max.y..map_max.y);
+    match rng.gen_bool(0.5) {
+        true => IVec2::new(side * far, along),
+        false => IVec2::new(along, side * far),
+    }
+}
+
+/// The bottom left corner of a rectangle of the given size, that starts
+/// outside of the spawn square and at most `reach` tiles away from the origin
+fn random_away_from_spawn(rng: &mut StdRng, reach: i32, size: IVec2) -> IVec2 {
+    let offset = IVec2::new(
+        rng.gen_range(SPAWN_RADIUS..reach),
+        rng.gen_range(SPAWN_RADIUS..reach),
+    );
+    // On the negative side, the rectangle has to extend away from the origin
+    IVec2::new(
+        match rng.gen_bool(0.5) {
+            true => offset.x,
+            false => -offset.x - size.x,
+        },
+        match rng.gen_bool(0.5) {
+            true => offset.y,
+            false => -offset.y - size.y,
+        },
+    )
+}
+
+/// Place a few buildings in the city center, where the ground allows it
+fn place_starter_buildings(
+    rng: &mut StdRng,
+    ground: &Ground,
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+) {
+    let mut placed: Vec<Vec2> = vec![];
+    for b_type in STARTER_BUILDINGS {
+        let size = get_size_from_type(&b_type);
+        for _ in 0..STARTER_TRIES {
+            let position = to_world_pos(IVec2::new(
+                rng.gen_range(-CITY_RADIUS..CITY_RADIUS),
+                rng.gen_range(-CITY_RADIUS..CITY_RADIUS),
+            ));
+            let far_enough = placed
+                .iter()
+                .all(|other| other.distance(position) > STARTER_SPACING);
+            if far_enough && ground.can_build(b_type, position, size) {
+                spawn_building(b_type, position, commands, asset_handles);
+                placed.push(position);
+                break;
+            }
+        }
+    }
+}
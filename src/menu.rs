@@ -1,6 +1,11 @@
 use bevy::prelude::*;
 
-use crate::states::GameState;
+use crate::{
+    debug::TEXT_SIZE,
+    mapgen::{MapSettings, MapSource},
+    scenario::{ActiveScenario, ScenarioProgress, Scenarios},
+    states::GameState,
+};
 
 #[derive(Component, Debug)]
 struct MenuUI;
@@ -10,22 +15,59 @@ pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
-            .add_systems(Update, click_buttons.run_if(in_state(GameState::MainMenu)))
-            .add_systems(Update, back_to_menu.run_if(in_state(GameState::Playing)))
-            .add_systems(OnExit(GameState::MainMenu), despawn_menu);
+            .add_systems(OnEnter(GameState::LevelSelect), spawn_level_select)
+            .add_systems(
+                Update,
+                (click_buttons, update_setting_labels).run_if(
+                    in_state(GameState::MainMenu).or_else(in_state(GameState::LevelSelect)),
+                ),
+            )
+            .add_systems(
+                Update,
+                back_to_menu
+                    .run_if(in_state(GameState::Playing).or_else(in_state(GameState::LevelSelect))),
+            )
+            .add_systems(OnExit(GameState::MainMenu), despawn_menu)
+            .add_systems(OnExit(GameState::LevelSelect), despawn_menu);
     }
 }
 
-fn spawn_main_menu(mut commands: Commands) {
-    // Spawn a rectangular button in the center of the screen that says "Play"
+/// What clicking a menu button does.
+#[derive(Component, Debug, Clone, Copy)]
+enum MenuButton {
+    Play,
+    Scenarios,
+    /// Start the scenario with this index
+    StartScenario(usize),
+    Back,
+    ToggleMap,
+    SeedDown,
+    SeedUp,
+    RandomSeed,
+    ToggleStarters,
+}
+
+/// A menu text showing the current value of a map setting.
+#[derive(Component, Debug, Clone, Copy)]
+enum SettingLabel {
+    Map,
+    Seed,
+    Starters,
+}
+
+fn spawn_main_menu(mut commands: Commands, settings: Res<MapSettings>) {
+    // Spawn a rectangular button in the center of the screen that says "Play",
+    // with the map settings below it
     commands
         .spawn((
             NodeBundle {
                 style: Style {
                     width: Val::Percent(100.0),
                     height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
                     align_items: AlignItems::Center,
                     justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(10.0),
                     ..default()
                 },
                 ..default()
@@ -33,45 +75,203 @@ fn spawn_main_menu(mut commands: Commands) {
             MenuUI,
         ))
         .with_children(|parent| {
+            spawn_button(parent, MenuButton::Play, "Play", 40.0, None);
+            spawn_button(parent, MenuButton::Scenarios, "Scenarios", TEXT_SIZE, None);
+            spawn_button(
+                parent,
+                MenuButton::ToggleMap,
+                &setting_text(SettingLabel::Map, &settings),
+                TEXT_SIZE,
+                Some(SettingLabel::Map),
+            );
             parent
-                .spawn(ButtonBundle {
+                .spawn(NodeBundle {
                     style: Style {
-                        width: Val::Px(200.0),
-                        height: Val::Px(70.0),
-                        border: UiRect::all(Val::Px(5.0)),
-                        justify_content: JustifyContent::Center,
                         align_items: AlignItems::Center,
+                        column_gap: Val::Px(10.0),
                         ..default()
                     },
-                    border_color: BorderColor(Color::GOLD),
-                    background_color: BackgroundColor(Color::GRAY),
                     ..default()
                 })
                 .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        "Play",
-                        TextStyle {
-                            font_size: 40.0,
-                            color: Color::BLUE,
-                            ..default()
-                        },
+                    parent.spawn((
+                        TextBundle::from_section(
+                            setting_text(SettingLabel::Seed, &settings),
+                            TextStyle {
+                                font_size: TEXT_SIZE,
+                                ..default()
+                            },
+                        ),
+                        SettingLabel::Seed,
                     ));
+                    spawn_button(parent, MenuButton::SeedDown, "-", TEXT_SIZE, None);
+                    spawn_button(parent, MenuButton::SeedUp, "+", TEXT_SIZE, None);
+                    spawn_button(parent, MenuButton::RandomSeed, "Random", TEXT_SIZE, None);
                 });
+            spawn_button(
+                parent,
+                MenuButton::ToggleStarters,
+                &setting_text(SettingLabel::Starters, &settings),
+                TEXT_SIZE,
+                Some(SettingLabel::Starters),
+            );
+        });
+}
+
+/// List the bundled scenarios, and which ones were completed
+fn spawn_level_select(
+    mut commands: Commands,
+    scenarios: Res<Scenarios>,
+    progress: Res<ScenarioProgress>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            MenuUI,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Scenarios",
+                TextStyle {
+                    font_size: 40.0,
+                    ..default()
+                },
+            ));
+            for (i, scenario) in scenarios.0.iter().enumerate() {
+                let completed = match progress.completed.contains(&scenario.name) {
+                    true => " (completed)",
+                    false => "",
+                };
+                spawn_button(
+                    parent,
+                    MenuButton::StartScenario(i),
+                    &format!("{}{}", scenario.name, completed),
+                    TEXT_SIZE,
+                    None,
+                );
+                parent.spawn(TextBundle::from_section(
+                    scenario.description.clone(),
+                    TextStyle {
+                        font_size: TEXT_SIZE - 8.0,
+                        ..default()
+                    },
+                ));
+            }
+            spawn_button(parent, MenuButton::Back, "Back", TEXT_SIZE, None);
         });
 }
 
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    button: MenuButton,
+    text: &str,
+    font_size: f32,
+    label: Option<SettingLabel>,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    min_width: Val::Px(font_size * 1.5),
+                    padding: UiRect::axes(Val::Px(font_size / 2.0), Val::Px(font_size / 4.0)),
+                    border: UiRect::all(Val::Px(5.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BorderColor(Color::GOLD),
+                background_color: BackgroundColor(Color::GRAY),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            let mut text = parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font_size,
+                    color: Color::BLUE,
+                    ..default()
+                },
+            ));
+            if let Some(label) = label {
+                text.insert(label);
+            }
+        });
+}
+
+fn setting_text(label: SettingLabel, settings: &MapSettings) -> String {
+    match label {
+        SettingLabel::Map => match settings.source {
+            MapSource::Handmade => "Map: Handmade".to_string(),
+            MapSource::Generated => "Map: Generated".to_string(),
+        },
+        SettingLabel::Seed => format!("Seed: {}", settings.seed),
+        SettingLabel::Starters => match settings.starter_buildings {
+            true => "Starter buildings: Yes".to_string(),
+            false => "Starter buildings: No".to_string(),
+        },
+    }
+}
+
 fn click_buttons(
-    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    interaction_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
     mut game_state: ResMut<NextState<GameState>>,
+    mut settings: ResMut<MapSettings>,
+    mut active_scenario: ResMut<ActiveScenario>,
+    scenarios: Res<Scenarios>,
 ) {
-    // Note: this works because there's only the Play button
-    for interaction in &interaction_query {
-        match *interaction {
-            Interaction::Pressed => {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            MenuButton::Play => {
+                // Sandbox mode
+                active_scenario.0 = None;
                 game_state.set(GameState::Playing);
             }
-            Interaction::Hovered => {}
-            Interaction::None => {}
+            MenuButton::Scenarios => game_state.set(GameState::LevelSelect),
+            MenuButton::StartScenario(i) => {
+                if let Some(scenario) = scenarios.0.get(*i) {
+                    *settings = scenario.map.clone();
+                    active_scenario.0 = Some(*i);
+                    game_state.set(GameState::Playing);
+                }
+            }
+            MenuButton::Back => game_state.set(GameState::MainMenu),
+            MenuButton::ToggleMap => {
+                settings.source = match settings.source {
+                    MapSource::Handmade => MapSource::Generated,
+                    MapSource::Generated => MapSource::Handmade,
+                };
+            }
+            MenuButton::SeedDown => settings.seed = settings.seed.saturating_sub(1),
+            MenuButton::SeedUp => settings.seed = settings.seed.saturating_add(1),
+            MenuButton::RandomSeed => settings.seed = rand::random::<u32>() as u64,
+            MenuButton::ToggleStarters => settings.starter_buildings = !settings.starter_buildings,
+        }
+    }
+}
+
+fn update_setting_labels(
+    settings: Res<MapSettings>,
+    mut labels_query: Query<(&mut Text, &SettingLabel)>,
+) {
+    if settings.is_changed() {
+        for (mut text, label) in &mut labels_query {
+            text.sections[0].value = setting_text(*label, &settings);
         }
     }
 }
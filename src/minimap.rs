@@ -0,0 +1,287 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    input::InputSystem,
+    prelude::*,
+    render::{camera::Viewport, view::RenderLayers},
+    window::PrimaryWindow,
+};
+
+use crate::{
+    building::{get_size_from_type, BuildingType},
+    camera::MainCamera,
+    ground::{TILES_RANGE_X, TILES_RANGE_Y, TILE_SIZE},
+    movement::desire_movement,
+    palette::Palette,
+    person::Person,
+    states::GameState,
+};
+
+/// The render layer only the minimap camera sees.
+const MINIMAP_LAYER: u8 = 1;
+/// The size of the minimap on screen, in logical pixels.
+const MINIMAP_SIZE: Vec2 = Vec2::new(240.0, 200.0);
+/// The space between the minimap and the bottom right corner of the window.
+const MINIMAP_MARGIN: Vec2 = Vec2::new(10.0, 50.0);
+/// Icons are drawn bigger than what they stand for, to stay readable once shrunk.
+const ICON_SCALE: f32 = 2.0;
+const PERSON_ICON_SIZE: Vec2 = Vec2::new(40.0, 40.0);
+
+// for z-ordering, within the minimap layer
+const BUILDING_ICON_LEVEL: f32 = 1.0;
+const PERSON_ICON_LEVEL: f32 = 2.0;
+const VIEW_RECT_LEVEL: f32 = 15.0;
+
+#[derive(Component, Debug)]
+pub struct MinimapCamera;
+
+#[derive(Component, Debug)]
+struct PersonIcon;
+
+/// The outline of what the main camera currently sees.
+#[derive(Component, Debug)]
+struct ViewRect;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::Playing),
+            (spawn_minimap_camera, spawn_view_rect),
+        )
+        .add_systems(
+            PreUpdate,
+            click_minimap
+                .after(InputSystem)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (
+                place_minimap_viewport,
+                // Adding children changes the order people and buildings are
+                // seen in, so only once the simulation is done with them
+                (add_building_icons, add_person_icons).after(desire_movement),
+                color_person_icons,
+                follow_main_camera,
+            )
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(OnExit(GameState::Playing), cleanup_minimap);
+    }
+}
+
+/// Spawn a second camera that sees the whole map, but only the minimap icons
+fn spawn_minimap_camera(mut commands: Commands) {
+    let map_size = Vec2::new(
+        TILES_RANGE_X.len() as f32 * TILE_SIZE.x,
+        TILES_RANGE_Y.len() as f32 * TILE_SIZE.y,
+    );
+    // Tiles are centered on their position, hence the half tile offset
+    let map_center = Vec2::new(
+        (TILES_RANGE_X.start + TILES_RANGE_X.end) as f32 * TILE_SIZE.x - TILE_SIZE.x,
+        (TILES_RANGE_Y.start + TILES_RANGE_Y.end) as f32 * TILE_SIZE.y - TILE_SIZE.y,
+    ) / 2.0;
+
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                // Render after the main camera
+                order: 1,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(Color::rgb(0.05, 0.05, 0.15)),
+            },
+            projection: OrthographicProjection {
+                scale: (map_size / MINIMAP_SIZE).max_element(),
+                // Like `Camera2dBundle`, so that icons in front of the camera are kept
+                near: -1000.0,
+                ..default()
+            },
+            transform: Transform::from_translation(map_center.extend(0.0)),
+            ..default()
+        },
+        RenderLayers::layer(MINIMAP_LAYER),
+        UiCameraConfig { show_ui: false },
+        MinimapCamera,
+    ));
+}
+
+fn spawn_view_rect(mut commands: Commands) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1.0, 1.0, 1.0, 0.25),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, VIEW_RECT_LEVEL),
+            ..default()
+        },
+        RenderLayers::layer(MINIMAP_LAYER),
+        ViewRect,
+    ));
+}
+
+/// Keep the minimap in the bottom right corner, whatever the window size
+fn place_minimap_viewport(
+    mut minimap_query: Query<&mut Camera, With<MinimapCamera>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    // Headless runs have no window
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let mut camera = minimap_query.single_mut();
+
+    let scale_factor = window.scale_factor() as f32;
+    let window_size = Vec2::new(
+        window.physical_width() as f32,
+        window.physical_height() as f32,
+    );
+    let size = MINIMAP_SIZE * scale_factor;
+    let position = (window_size - size - MINIMAP_MARGIN * scale_factor).max(Vec2::ZERO);
+
+    camera.viewport = Some(Viewport {
+        physical_position: position.as_uvec2(),
+        physical_size: size.as_uvec2().min(window_size.as_uvec2()),
+        ..default()
+    });
+}
+
+/// Give every new building a minimap icon, coloured by type
+fn add_building_icons(
+    mut commands: Commands,
+    buildings_query: Query<(Entity, &BuildingType), Added<BuildingType>>,
+) {
+    for (entity, b_type) in &buildings_query {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: icon_color(b_type),
+                        custom_size: Some(get_size_from_type(b_type) * ICON_SCALE),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, BUILDING_ICON_LEVEL),
+                    ..default()
+                },
+                RenderLayers::layer(MINIMAP_LAYER),
+            ));
+        });
+    }
+}
+
+fn add_person_icons(mut commands: Commands, persons_query: Query<Entity, Added<Person>>) {
+    for entity in &persons_query {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(PERSON_ICON_SIZE),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, PERSON_ICON_LEVEL),
+                    ..default()
+                },
+                RenderLayers::layer(MINIMAP_LAYER),
+                PersonIcon,
+            ));
+        });
+    }
+}
+
+/// Colour people's dots along the palette, from unhappy to happy
+fn color_person_icons(
+    palette: Res<Palette>,
+    mut icons_query: Query<(&Parent, &mut Sprite), With<PersonIcon>>,
+    persons_query: Query<&Person>,
+) {
+    for (parent, mut sprite) in &mut icons_query {
+        if let Ok(person) = persons_query.get(parent.get()) {
+            sprite.color = palette.gradient(person.satisfaction / 100.0);
+        }
+    }
+}
+
+/// Make the view rectangle match what the main camera sees
+#[allow(clippy::type_complexity)]
+fn follow_main_camera(
+    camera_query: Query<(&Camera, &Transform), With<MainCamera>>,
+    mut rect_query: Query<(&mut Transform, &mut Sprite), (With<ViewRect>, Without<MainCamera>)>,
+) {
+    let (camera, cam_transform) = camera_query.single();
+    let (mut rect_transform, mut sprite) = rect_query.single_mut();
+
+    rect_transform.translation.x = cam_transform.translation.x;
+    rect_transform.translation.y = cam_transform.translation.y;
+    sprite.custom_size = camera
+        .logical_viewport_size()
+        .map(|size| size * cam_transform.scale.truncate());
+}
+
+/// Move the main camera to wherever the minimap is clicked, and
+/// keep the click from reaching the world underneath
+pub fn click_minimap(
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut panning: Local<bool>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    minimap_query: Query<(&Camera, &GlobalTransform), With<MinimapCamera>>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !buttons.pressed(MouseButton::Left) {
+        *panning = false;
+        return;
+    }
+    let Ok((minimap, minimap_transform)) = minimap_query.get_single() else {
+        return;
+    };
+    let Some(cursor) = window_query
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+    let Some(rect) = minimap.logical_viewport_rect() else {
+        return;
+    };
+
+    if buttons.just_pressed(MouseButton::Left) && rect.contains(cursor) {
+        *panning = true;
+        buttons.clear_just_pressed(MouseButton::Left);
+    }
+    if *panning {
+        let clamped = cursor.clamp(rect.min, rect.max);
+        if let Some(world_pos) = minimap.viewport_to_world_2d(minimap_transform, clamped - rect.min)
+        {
+            let mut cam_transform = camera_query.single_mut();
+            cam_transform.translation.x = world_pos.x;
+            cam_transform.translation.y = world_pos.y;
+        }
+    }
+}
+
+fn icon_color(b_type: &BuildingType) -> Color {
+    match b_type {
+        BuildingType::House => Color::ORANGE,
+        BuildingType::Forum => Color::GOLD,
+        BuildingType::Cinema => Color::PURPLE,
+        BuildingType::Hospital => Color::WHITE,
+        BuildingType::Pool => Color::CYAN,
+        BuildingType::Restaurant => Color::CRIMSON,
+        BuildingType::Creative => Color::PINK,
+        BuildingType::Tree => Color::DARK_GREEN,
+        BuildingType::Lamp => Color::GRAY,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cleanup_minimap(
+    mut commands: Commands,
+    minimap_query: Query<Entity, Or<(With<MinimapCamera>, With<ViewRect>)>>,
+) {
+    for entity in &minimap_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -6,7 +6,14 @@ use rand::{
     Rng,
 };
 
-use crate::{building::BuildingType, person::Person, states::GameState};
+use crate::{
+    building::BuildingType,
+    drag::find_hovered,
+    events::Burning,
+    ground::Ground,
+    person::{hitbox_follow, Person, SimRng},
+    states::GameState,
+};
 
 /// A general scalar applied to all movements.
 const BASE_MOVEMENT_SCALAR: f32 = 2.2;
@@ -27,7 +34,7 @@ pub const SPORT_THRESHOLD: f32 = 20.0;
 pub const CREAT_THRESHOLD: f32 = 20.0;
 
 #[derive(Resource, Debug)]
-struct MovementScalar(f32);
+pub struct MovementScalar(f32);
 
 /// The idle movement direction.
 #[derive(Debug, Default, Clone, Copy)]
@@ -68,9 +75,12 @@ impl Plugin for MovementPlugin {
             TimerMode::Repeating,
         )))
         .insert_resource(MovementScalar(BASE_MOVEMENT_SCALAR))
+        .add_systems(OnEnter(GameState::Playing), reset_movement)
         .add_systems(
             PreUpdate,
-            reset_movement_vector.run_if(in_state(GameState::Playing)),
+            reset_movement_vector
+                .before(find_hovered)
+                .run_if(in_state(GameState::Playing)),
         )
         .add_systems(
             Update,
@@ -80,6 +90,8 @@ impl Plugin for MovementPlugin {
                 social_movement,
                 desire_movement,
             )
+                .chain()
+                .after(hitbox_follow)
                 .run_if(in_state(GameState::Playing)),
         )
         .add_systems(
@@ -89,6 +101,14 @@ impl Plugin for MovementPlugin {
     }
 }
 
+fn reset_movement(
+    mut dir_timer: ResMut<IdleDirectionTimer>,
+    mut movt_scalar: ResMut<MovementScalar>,
+) {
+    dir_timer.0.reset();
+    movt_scalar.0 = BASE_MOVEMENT_SCALAR;
+}
+
 fn reset_movement_vector(mut person_query: Query<&mut Person>) {
     for mut person in &mut person_query {
         person.movement_vector = Vec2::ZERO;
@@ -107,11 +127,14 @@ fn move_idle_persons(
     mut persons_query: Query<&mut Person>,
     time: Res<Time>,
     mut dir_timer: ResMut<IdleDirectionTimer>,
+    mut sim_rng: ResMut<SimRng>,
 ) {
     if dir_timer.0.tick(time.delta()).just_finished() {
         // change the directions at random
-        for mut person in &mut persons_query {
-            let new_dir: MovementDir = rand::random();
+        let mut persons: Vec<Mut<Person>> = persons_query.iter_mut().collect();
+        persons.sort_by_key(|person| person.id);
+        for mut person in persons {
+            let new_dir: MovementDir = sim_rng.0.gen();
             person.movement_direction = new_dir;
         }
     }
@@ -163,51 +186,43 @@ fn move_relative_to(
 }
 
 fn social_movement(mut persons_query: Query<(&mut Person, &Transform)>) {
-    let mut combinations = persons_query.iter_combinations_mut();
-    while let Some([(mut person_1, transform_1), (mut person_2, transform_2)]) =
-        combinations.fetch_next()
-    {
-        // If person X likes person Y, move them towards person Y and vice-versa
-        if person_1.liked.contains(&person_2.id) {
-            move_relative_to(
-                &mut person_1,
-                transform_1,
-                transform_2,
-                true,
-                SOCIAL_INTERACT,
-            );
-        } else if person_1.disliked.contains(&person_2.id) {
-            move_relative_to(
-                &mut person_1,
-                transform_1,
-                transform_2,
-                false,
-                SOCIAL_INTERACT,
-            );
-        }
-        if person_2.liked.contains(&person_1.id) {
-            move_relative_to(
-                &mut person_2,
-                transform_2,
-                transform_1,
-                true,
-                SOCIAL_INTERACT,
-            );
-        } else if person_2.disliked.contains(&person_1.id) {
-            move_relative_to(
-                &mut person_2,
-                transform_2,
-                transform_1,
-                false,
-                SOCIAL_INTERACT,
-            );
+    // Go through the others by id, so that the pulls add up the same way every game
+    let mut others: Vec<(i32, Transform)> = persons_query
+        .iter()
+        .map(|(person, transform)| (person.id, *transform))
+        .collect();
+    others.sort_by_key(|(id, _)| *id);
+
+    for (mut person, transform) in &mut persons_query {
+        for (other_id, other_transform) in &others {
+            if *other_id == person.id {
+                continue;
+            }
+            // If person X likes person Y, move them towards person Y, away if they don't
+            if person.liked.contains(other_id) {
+                move_relative_to(
+                    &mut person,
+                    transform,
+                    other_transform,
+                    true,
+                    SOCIAL_INTERACT,
+                );
+            } else if person.disliked.contains(other_id) {
+                move_relative_to(
+                    &mut person,
+                    transform,
+                    other_transform,
+                    false,
+                    SOCIAL_INTERACT,
+                );
+            }
         }
     }
 }
 
-fn desire_movement(
+pub fn desire_movement(
     mut persons_query: Query<(&mut Person, &Transform)>,
-    buildings_query: Query<(&BuildingType, &Transform)>,
+    buildings_query: Query<(&BuildingType, &Transform), Without<Burning>>,
 ) {
     // If problem, move towards the closest building that solves the problem
     for (mut person, p_transform) in &mut persons_query {
@@ -280,7 +295,7 @@ fn desire_movement(
 fn get_closest_of_interest(
     p_transform: &Transform,
     desired_type: BuildingType,
-    buildings_query: &Query<(&BuildingType, &Transform)>,
+    buildings_query: &Query<(&BuildingType, &Transform), Without<Burning>>,
 ) -> Transform {
     // Get the transform of the closest building of the given type
     let mut closest_of_interest = p_transform;
@@ -297,10 +312,11 @@ fn get_closest_of_interest(
     *closest_of_interest
 }
 
-fn resolve_movements(
+pub fn resolve_movements(
     mut person_query: Query<(&mut Person, &mut Transform)>,
     time: Res<Time>,
     movt_scalar: Res<MovementScalar>,
+    ground: Res<Ground>,
 ) {
     // At the end of the frame, apply the final movt vector
     for (mut person, mut transform) in &mut person_query {
@@ -308,9 +324,97 @@ fn resolve_movements(
             // This is here to avoid NaN values when using clamp_length with a non-zero min
             person.movement_vector += Vec2::new(0.01, 0.01);
         }
-        person.movement_vector =
-            person.movement_vector.clamp_length(0.25, 6.5) * movt_scalar.0 * time.delta_seconds();
+        // Walk faster on roads
+        let terrain_speed = ground
+            .tile_at(transform.translation.truncate())
+            .map_or(1.0, |tile| tile.terrain.speed());
+        person.movement_vector = person.movement_vector.clamp_length(0.25, 6.5)
+            * movt_scalar.0
+            * terrain_speed
+            * time.delta_seconds();
         transform.translation.x += person.movement_vector.x;
         transform.translation.y += person.movement_vector.y;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_app;
+
+    #[test]
+    fn hungry_person_walks_to_nearest_restaurant() {
+        let mut app = test_app();
+        app.init_resource::<Ground>()
+            .insert_resource(MovementScalar(BASE_MOVEMENT_SCALAR))
+            .add_systems(PreUpdate, reset_movement_vector)
+            .add_systems(Update, desire_movement)
+            .add_systems(PostUpdate, resolve_movements);
+        // Only hunger is a problem
+        let person = app
+            .world
+            .spawn((
+                Person {
+                    hunger: 0.0,
+                    shelter: 100.0,
+                    social: 100.0,
+                    entertained: 100.0,
+                    health: 100.0,
+                    sport: 100.0,
+                    creativity: 100.0,
+                    ..default()
+                },
+                Transform::default(),
+            ))
+            .id();
+        let nearest = Vec3::new(120.0, 90.0, 0.0);
+        let farthest = Vec3::new(-300.0, 0.0, 0.0);
+        for position in [nearest, farthest] {
+            app.world.spawn((
+                BuildingType::Restaurant,
+                Transform::from_translation(position),
+            ));
+        }
+
+        for _ in 0..10 {
+            app.update();
+        }
+        let position = app.world.get::<Transform>(person).unwrap().translation;
+        assert!(position.distance(nearest) < nearest.length());
+        assert!(position.distance(farthest) > farthest.length());
+        // Straight there
+        assert!(position.normalize().dot(nearest.normalize()) > 0.99);
+    }
+
+    #[test]
+    fn fed_person_ignores_restaurants() {
+        let mut app = test_app();
+        app.add_systems(Update, desire_movement);
+        let person = app
+            .world
+            .spawn((
+                Person {
+                    hunger: 100.0,
+                    shelter: 100.0,
+                    social: 100.0,
+                    entertained: 100.0,
+                    health: 100.0,
+                    sport: 100.0,
+                    creativity: 100.0,
+                    ..default()
+                },
+                Transform::default(),
+            ))
+            .id();
+        app.world.spawn((
+            BuildingType::Restaurant,
+            Transform::from_xyz(120.0, 90.0, 0.0),
+        ));
+
+        app.update();
+        assert_eq!(
+            app.world.get::<Person>(person).unwrap().movement_vector,
+            Vec2::ZERO
+        );
+    }
+}
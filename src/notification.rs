@@ -0,0 +1,538 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    input::{mouse::MouseWheel, InputSystem},
+    prelude::*,
+    ui::UiSystem,
+};
+
+use crate::{
+    camera::MainCamera, daynight::Clock, debug::TEXT_SIZE, minimap::click_minimap,
+    states::GameState,
+};
+
+/// How long a toast stays on screen, in seconds.
+const TOAST_DURATION: f32 = 4.0;
+/// How long a toast takes to fade out, at the end of its life, in seconds.
+const TOAST_FADE: f32 = 1.0;
+/// The maximum number of toasts shown at once; the oldest ones go first.
+const MAX_TOASTS: usize = 5;
+/// The maximum number of entries kept in the log; the oldest ones go first.
+const MAX_LOG_ENTRIES: usize = 100;
+/// How far a notch of the mouse wheel scrolls the log, in pixels.
+const LOG_SCROLL_STEP: f32 = 20.0;
+const LOG_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.7);
+
+/// What a notification is about, to filter the log by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Placing, moving and demolishing buildings, and their stock
+    #[default]
+    Building,
+    Arrival,
+    Health,
+    /// Epidemics, heatwaves, festivals and fires
+    Event,
+    /// Unlocks and milestones
+    Progress,
+}
+
+impl Category {
+    const ALL: [Category; 5] = [
+        Category::Building,
+        Category::Arrival,
+        Category::Health,
+        Category::Event,
+        Category::Progress,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Category::Building => "Buildings",
+            Category::Arrival => "Arrivals",
+            Category::Health => "Health",
+            Category::Event => "Events",
+            Category::Progress => "Progress",
+        }
+    }
+
+    /// Returns `false` for notifications frequent enough to only go to the log
+    fn toasts(self) -> bool {
+        self != Category::Arrival
+    }
+}
+
+/// Something worth telling the player about.
+#[derive(Event, Debug, Clone)]
+pub struct Notification {
+    pub message: String,
+    pub category: Category,
+    /// What it is about, for the log to take the camera there
+    pub subject: Option<Entity>,
+}
+
+impl Notification {
+    pub fn new(category: Category, message: impl Into<String>) -> Self {
+        Notification {
+            message: message.into(),
+            category,
+            subject: None,
+        }
+    }
+
+    pub fn about(mut self, subject: Entity) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+}
+
+/// A notification kept in the log.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// When it happened, as the clock read
+    pub timestamp: String,
+    pub message: String,
+    pub category: Category,
+    pub subject: Option<Entity>,
+    /// Where the subject was, in case it is gone when the entry is clicked
+    pub position: Option<Vec2>,
+}
+
+/// Every recent notification, the latest last, reset when a game starts.
+#[derive(Resource, Debug, Default)]
+pub struct NotificationLog {
+    pub entries: VecDeque<LogEntry>,
+}
+
+/// What the log panel shows.
+#[derive(Resource, Debug, Default)]
+struct LogView {
+    shown: bool,
+    /// The only category shown, or `None` for all of them
+    filter: Option<Category>,
+    /// How far the entries are scrolled, in pixels from the top
+    scroll: f32,
+}
+
+/// The column toasts are stacked in.
+#[derive(Component, Debug)]
+struct ToastStack;
+
+#[derive(Component, Debug)]
+struct Toast(Timer);
+
+/// Marker for every part of the log panel, which catches the clicks made on it.
+#[derive(Component, Debug)]
+struct LogUi;
+
+#[derive(Component, Debug)]
+struct LogPanel;
+
+/// The column entries are listed in, moved up and down to scroll.
+#[derive(Component, Debug)]
+struct LogEntries;
+
+#[derive(Component, Debug)]
+struct CategoryTab(Option<Category>);
+
+/// A line of the log, with what its entry is about.
+/// Copied rather than indexed, as dropping old entries shifts the indices.
+#[derive(Component, Debug)]
+struct LogLine {
+    subject: Option<Entity>,
+    position: Option<Vec2>,
+}
+
+pub struct NotificationPlugin;
+
+impl Plugin for NotificationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Notification>()
+            .init_resource::<NotificationLog>()
+            .init_resource::<LogView>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (reset_log, spawn_toast_stack, spawn_log_panel),
+            )
+            .add_systems(
+                PreUpdate,
+                catch_log_clicks
+                    .after(InputSystem)
+                    .after(UiSystem::Focus)
+                    .before(click_minimap)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    show_toasts,
+                    fade_toasts,
+                    (
+                        record_notifications,
+                        toggle_log,
+                        pick_category,
+                        scroll_log,
+                        list_entries,
+                        go_to_entry,
+                    )
+                        .chain(),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_toasts);
+    }
+}
+
+fn reset_log(mut log: ResMut<NotificationLog>, mut view: ResMut<LogView>) {
+    log.entries.clear();
+    view.scroll = 0.0;
+}
+
+fn spawn_toast_stack(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::VMin(1.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        },
+        ToastStack,
+    ));
+}
+
+/// Turn new notifications into toasts at the bottom of the stack
+fn show_toasts(
+    mut commands: Commands,
+    mut notifications: EventReader<Notification>,
+    stack_query: Query<(Entity, Option<&Children>), With<ToastStack>>,
+) {
+    let Ok((stack, children)) = stack_query.get_single() else {
+        return;
+    };
+    let mut shown: Vec<Entity> = children.map_or(vec![], |children| children.to_vec());
+
+    for notification in notifications.read() {
+        if !notification.category.toasts() {
+            continue;
+        }
+        if shown.len() >= MAX_TOASTS {
+            let oldest = shown.remove(0);
+            commands.entity(oldest).despawn_recursive();
+        }
+        let toast = commands
+            .spawn((
+                TextBundle::from_section(
+                    notification.message.clone(),
+                    TextStyle {
+                        font_size: TEXT_SIZE - 6.0,
+                        color: Color::GOLD,
+                        ..default()
+                    },
+                )
+                .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.6)),
+                Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once)),
+            ))
+            .id();
+        commands.entity(stack).add_child(toast);
+        shown.push(toast);
+    }
+}
+
+/// Fade toasts out at the end of their life, then remove them
+fn fade_toasts(
+    mut commands: Commands,
+    mut toasts_query: Query<(Entity, &mut Toast, &mut Text, &mut BackgroundColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut toast, mut text, mut background) in &mut toasts_query {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let alpha = (toast.0.remaining_secs() / TOAST_FADE).min(1.0);
+        for section in &mut text.sections {
+            section.style.color.set_a(alpha);
+        }
+        background.0.set_a(0.6 * alpha);
+    }
+}
+
+/// Keep every notification in the log, with the time and place it happened
+fn record_notifications(
+    mut log: ResMut<NotificationLog>,
+    mut notifications: EventReader<Notification>,
+    clock: Res<Clock>,
+    transforms_query: Query<&GlobalTransform>,
+) {
+    let minutes = (clock.hour * 60.0) as u32;
+    let timestamp = format!("D{} {:02}:{:02}", clock.day, minutes / 60, minutes % 60);
+
+    for notification in notifications.read() {
+        if log.entries.len() >= MAX_LOG_ENTRIES {
+            log.entries.pop_front();
+        }
+        let position = notification
+            .subject
+            .and_then(|subject| transforms_query.get(subject).ok())
+            .map(|transform| transform.translation().truncate());
+        log.entries.push_back(LogEntry {
+            timestamp: timestamp.clone(),
+            message: notification.message.clone(),
+            category: notification.category,
+            subject: notification.subject,
+            position,
+        });
+    }
+}
+
+fn spawn_log_panel(mut commands: Commands, view: Res<LogView>) {
+    let tab_style = TextStyle {
+        font_size: TEXT_SIZE - 12.0,
+        ..default()
+    };
+    let tabs = [None]
+        .into_iter()
+        .chain(Category::ALL.map(Some))
+        .collect::<Vec<_>>();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: match view.shown {
+                        true => Display::Flex,
+                        false => Display::None,
+                    },
+                    position_type: PositionType::Absolute,
+                    // Above the minimap
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(260.0),
+                    width: Val::Px(380.0),
+                    height: Val::Px(260.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: LOG_BACKGROUND.into(),
+                ..default()
+            },
+            Interaction::default(),
+            LogPanel,
+            LogUi,
+        ))
+        .with_children(|panel| {
+            panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_wrap: FlexWrap::Wrap,
+                        column_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    for category in tabs {
+                        let label = category.map_or("All", Category::label);
+                        row.spawn((ButtonBundle::default(), CategoryTab(category), LogUi))
+                            .with_children(|tab| {
+                                tab.spawn(TextBundle::from_section(label, tab_style.clone()));
+                            });
+                    }
+                });
+            panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_grow: 1.0,
+                        overflow: Overflow::clip_y(),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|clip| {
+                    clip.spawn((
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                width: Val::Percent(100.0),
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        LogEntries,
+                    ));
+                });
+        });
+}
+
+/// J shows or hides the log
+fn toggle_log(
+    keys: Res<Input<KeyCode>>,
+    mut view: ResMut<LogView>,
+    mut panel_query: Query<&mut Style, With<LogPanel>>,
+) {
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        || !keys.just_pressed(KeyCode::J)
+    {
+        return;
+    }
+    view.shown = !view.shown;
+    for mut style in &mut panel_query {
+        style.display = match view.shown {
+            true => Display::Flex,
+            false => Display::None,
+        };
+    }
+}
+
+/// Keep clicks on the log from reaching the city behind it
+fn catch_log_clicks(
+    mut buttons: ResMut<Input<MouseButton>>,
+    view: Res<LogView>,
+    log_ui_query: Query<&Interaction, With<LogUi>>,
+) {
+    let on_log = log_ui_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if view.shown && on_log {
+        buttons.clear_just_pressed(MouseButton::Left);
+    }
+}
+
+/// Filter the log by the clicked tab, and highlight it
+fn pick_category(
+    mut view: ResMut<LogView>,
+    mut tabs_query: Query<(Ref<Interaction>, &CategoryTab, &mut BackgroundColor)>,
+) {
+    for (interaction, tab, _) in &tabs_query {
+        if interaction.is_changed() && *interaction == Interaction::Pressed {
+            view.filter = tab.0;
+            view.scroll = 0.0;
+        }
+    }
+    for (_, tab, mut background) in &mut tabs_query {
+        background.0 = match tab.0 == view.filter {
+            true => Color::DARK_GRAY,
+            false => Color::NONE,
+        };
+    }
+}
+
+/// Scroll the log with the mouse wheel, while the cursor is over it
+fn scroll_log(
+    mut view: ResMut<LogView>,
+    mut wheel: EventReader<MouseWheel>,
+    panel_query: Query<&Interaction, With<LogPanel>>,
+    entries_query: Query<(&Node, &Parent), With<LogEntries>>,
+    nodes_query: Query<&Node>,
+) {
+    let hovered = panel_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    let scrolled: f32 = wheel.read().map(|event| event.y).sum();
+    if !hovered || scrolled == 0.0 {
+        return;
+    }
+    let Ok((entries, parent)) = entries_query.get_single() else {
+        return;
+    };
+    let visible = nodes_query
+        .get(parent.get())
+        .map_or(0.0, |clip| clip.size().y);
+    let max_scroll = (entries.size().y - visible).max(0.0);
+    view.scroll = (view.scroll - scrolled * LOG_SCROLL_STEP).clamp(0.0, max_scroll);
+}
+
+/// List the entries the filter lets through, the latest on top
+fn list_entries(
+    mut commands: Commands,
+    log: Res<NotificationLog>,
+    view: Res<LogView>,
+    mut entries_query: Query<(Entity, &mut Style), With<LogEntries>>,
+) {
+    let Ok((entries, mut style)) = entries_query.get_single_mut() else {
+        return;
+    };
+    style.top = Val::Px(-view.scroll);
+    if !log.is_changed() && !view.is_changed() {
+        return;
+    }
+
+    commands.entity(entries).despawn_descendants();
+    let lines: Vec<Entity> = log
+        .entries
+        .iter()
+        .rev()
+        .filter(|entry| view.filter.is_none_or(|filter| filter == entry.category))
+        .map(|entry| {
+            let text_style = TextStyle {
+                font_size: TEXT_SIZE - 12.0,
+                ..default()
+            };
+            commands
+                .spawn((
+                    ButtonBundle {
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    },
+                    LogLine {
+                        subject: entry.subject,
+                        position: entry.position,
+                    },
+                    LogUi,
+                ))
+                .with_children(|line| {
+                    line.spawn(TextBundle::from_sections([
+                        TextSection::new(
+                            format!("{} ", entry.timestamp),
+                            TextStyle {
+                                color: Color::GRAY,
+                                ..text_style.clone()
+                            },
+                        ),
+                        TextSection::new(entry.message.clone(), text_style),
+                    ]));
+                })
+                .id()
+        })
+        .collect();
+    commands.entity(entries).push_children(&lines);
+}
+
+/// Take the camera to what a clicked entry is about
+fn go_to_entry(
+    lines_query: Query<(&Interaction, &LogLine), Changed<Interaction>>,
+    transforms_query: Query<&GlobalTransform>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    for (interaction, line) in &lines_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let position = line
+            .subject
+            .and_then(|subject| transforms_query.get(subject).ok())
+            .map(|transform| transform.translation().truncate())
+            .or(line.position);
+        if let Some(position) = position {
+            let mut camera = camera_query.single_mut();
+            camera.translation.x = position.x;
+            camera.translation.y = position.y;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cleanup_toasts(
+    mut commands: Commands,
+    stack_query: Query<Entity, Or<(With<ToastStack>, With<LogPanel>)>>,
+) {
+    for entity in &stack_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    building::BuildingType,
+    debug::TEXT_SIZE,
+    movement::{
+        CREAT_THRESHOLD, ENTERT_THRESHOLD, HEALTH_THRESHOLD, HUNGER_THRESHOLD, SHELTER_THRESHOLD,
+        SOCIAL_THRESHOLD, SPORT_THRESHOLD,
+    },
+    palette::Palette,
+    person::{Person, INTERACTION_DISTANCE},
+    states::GameState,
+};
+
+/// The side of a heatmap cell, in world units.
+const HEATMAP_CELL: f32 = 64.0;
+/// The radius of the circle drawn around people lacking the overlay's need.
+const PERSON_MARK_RADIUS: f32 = 10.0;
+
+// for z-ordering, between the ground and the buildings
+const HEATMAP_LEVEL: f32 = 0.5;
+
+/// What is currently drawn over the city.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub enum OverlayMode {
+    #[default]
+    Off,
+    Satisfaction,
+    Coverage(BuildingType),
+}
+
+impl OverlayMode {
+    /// The mode coming after this one when cycling through overlays
+    fn next(self) -> Self {
+        match self {
+            OverlayMode::Off => OverlayMode::Satisfaction,
+            OverlayMode::Satisfaction => OverlayMode::Coverage(BuildingType::House),
+            OverlayMode::Coverage(BuildingType::House) => {
+                OverlayMode::Coverage(BuildingType::Restaurant)
+            }
+            OverlayMode::Coverage(BuildingType::Restaurant) => {
+                OverlayMode::Coverage(BuildingType::Forum)
+            }
+            OverlayMode::Coverage(BuildingType::Forum) => {
+                OverlayMode::Coverage(BuildingType::Cinema)
+            }
+            OverlayMode::Coverage(BuildingType::Cinema) => {
+                OverlayMode::Coverage(BuildingType::Hospital)
+            }
+            OverlayMode::Coverage(BuildingType::Hospital) => {
+                OverlayMode::Coverage(BuildingType::Pool)
+            }
+            OverlayMode::Coverage(BuildingType::Pool) => {
+                OverlayMode::Coverage(BuildingType::Creative)
+            }
+            OverlayMode::Coverage(_) => OverlayMode::Off,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            OverlayMode::Off => "Off",
+            OverlayMode::Satisfaction => "Satisfaction",
+            OverlayMode::Coverage(BuildingType::House) => "Shelter coverage",
+            OverlayMode::Coverage(BuildingType::Restaurant) => "Hunger coverage",
+            OverlayMode::Coverage(BuildingType::Forum) => "Social coverage",
+            OverlayMode::Coverage(BuildingType::Cinema) => "Entertainment coverage",
+            OverlayMode::Coverage(BuildingType::Hospital) => "Health coverage",
+            OverlayMode::Coverage(BuildingType::Pool) => "Sport coverage",
+            OverlayMode::Coverage(BuildingType::Creative) => "Creativity coverage",
+            OverlayMode::Coverage(_) => "Coverage",
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+struct HeatmapTimer(Timer);
+
+#[derive(Component, Debug)]
+struct HeatmapCell;
+
+#[derive(Component, Debug)]
+struct OverlayText;
+
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OverlayMode>()
+            .insert_resource(HeatmapTimer(Timer::from_seconds(0.5, TimerMode::Repeating)))
+            .add_systems(OnEnter(GameState::Playing), spawn_overlay_text)
+            .add_systems(
+                Update,
+                (
+                    switch_overlay,
+                    update_overlay_text,
+                    draw_heatmap,
+                    draw_coverage,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                OnExit(GameState::Playing),
+                (cleanup_overlay, reset_overlay_mode),
+            );
+    }
+}
+
+fn spawn_overlay_text(mut commands: Commands, mode: Res<OverlayMode>) {
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Overlay (V): ",
+                TextStyle {
+                    font_size: TEXT_SIZE - 6.0,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                mode.label(),
+                TextStyle {
+                    font_size: TEXT_SIZE - 6.0,
+                    color: Color::GOLD,
+                    ..default()
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::VMax(1.0),
+            top: Val::VMin(7.0),
+            ..default()
+        }),
+        OverlayText,
+    ));
+}
+
+fn switch_overlay(keys: Res<Input<KeyCode>>, mut mode: ResMut<OverlayMode>) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if keys.just_pressed(KeyCode::V) && !ctrl {
+        *mode = mode.next();
+    }
+}
+
+fn update_overlay_text(
+    mode: Res<OverlayMode>,
+    mut text_query: Query<&mut Text, With<OverlayText>>,
+) {
+    if mode.is_changed() {
+        text_query.single_mut().sections[1].value = mode.label().to_string();
+    }
+}
+
+/// Tint the ground under people from bad to good, by average satisfaction
+fn draw_heatmap(
+    mut commands: Commands,
+    mode: Res<OverlayMode>,
+    palette: Res<Palette>,
+    time: Res<Time>,
+    mut timer: ResMut<HeatmapTimer>,
+    persons_query: Query<(&Person, &Transform)>,
+    cells_query: Query<Entity, With<HeatmapCell>>,
+) {
+    let refresh = timer.0.tick(time.delta()).just_finished();
+    if *mode != OverlayMode::Satisfaction {
+        if mode.is_changed() {
+            for entity in &cells_query {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        return;
+    }
+    if !refresh && !mode.is_changed() && !palette.is_changed() {
+        return;
+    }
+
+    for entity in &cells_query {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // Sum and count of satisfactions in each cell
+    let mut cells: HashMap<IVec2, (f32, f32)> = HashMap::new();
+    for (person, transform) in &persons_query {
+        let cell = (transform.translation.truncate() / HEATMAP_CELL)
+            .floor()
+            .as_ivec2();
+        let entry = cells.entry(cell).or_insert((0.0, 0.0));
+        entry.0 += person.satisfaction;
+        entry.1 += 1.0;
+    }
+
+    for (cell, (sum, count)) in cells {
+        let ratio = sum / count / 100.0;
+        let center = (cell.as_vec2() + Vec2::splat(0.5)) * HEATMAP_CELL;
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: palette.gradient(ratio).with_a(0.4),
+                    custom_size: Some(Vec2::splat(HEATMAP_CELL)),
+                    ..default()
+                },
+                transform: Transform::from_translation(center.extend(HEATMAP_LEVEL)),
+                ..default()
+            },
+            HeatmapCell,
+        ));
+    }
+}
+
+/// Circle the reach of every building serving the overlay's need, and
+/// mark the people who currently lack it
+fn draw_coverage(
+    mut gizmos: Gizmos,
+    mode: Res<OverlayMode>,
+    palette: Res<Palette>,
+    buildings_query: Query<(&BuildingType, &Transform)>,
+    persons_query: Query<(&Person, &Transform)>,
+) {
+    let OverlayMode::Coverage(covered_type) = *mode else {
+        return;
+    };
+
+    for (b_type, transform) in &buildings_query {
+        if *b_type == covered_type {
+            let position = transform.translation.truncate();
+            gizmos.circle_2d(position, INTERACTION_DISTANCE, palette.good());
+        }
+    }
+    for (person, transform) in &persons_query {
+        if lacks_need(person, covered_type) {
+            gizmos.circle_2d(
+                transform.translation.truncate(),
+                PERSON_MARK_RADIUS,
+                palette.bad(),
+            );
+        }
+    }
+}
+
+/// The person's level of the need the given building type satisfies, if any
+pub fn need_level(person: &Person, b_type: BuildingType) -> Option<f32> {
+    match b_type {
+        BuildingType::House => Some(person.shelter),
+        BuildingType::Restaurant => Some(person.hunger),
+        BuildingType::Forum => Some(person.social),
+        BuildingType::Cinema => Some(person.entertained),
+        BuildingType::Hospital => Some(person.health),
+        BuildingType::Pool => Some(person.sport),
+        BuildingType::Creative => Some(person.creativity),
+        BuildingType::Tree | BuildingType::Lamp => None,
+    }
+}
+
+/// The level under which a need drives people to the given building type
+pub fn need_threshold(b_type: BuildingType) -> Option<f32> {
+    match b_type {
+        BuildingType::House => Some(SHELTER_THRESHOLD),
+        BuildingType::Restaurant => Some(HUNGER_THRESHOLD),
+        BuildingType::Forum => Some(SOCIAL_THRESHOLD),
+        BuildingType::Cinema => Some(ENTERT_THRESHOLD),
+        BuildingType::Hospital => Some(HEALTH_THRESHOLD),
+        BuildingType::Pool => Some(SPORT_THRESHOLD),
+        BuildingType::Creative => Some(CREAT_THRESHOLD),
+        BuildingType::Tree | BuildingType::Lamp => None,
+    }
+}
+
+/// Returns `true` if the person is under the threshold of the need
+/// the given building type satisfies
+pub fn lacks_need(person: &Person, b_type: BuildingType) -> bool {
+    match (need_level(person, b_type), need_threshold(b_type)) {
+        (Some(level), Some(threshold)) => level < threshold,
+        _ => false,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cleanup_overlay(
+    mut commands: Commands,
+    overlay_query: Query<Entity, Or<(With<HeatmapCell>, With<OverlayText>)>>,
+) {
+    for entity in &overlay_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn reset_overlay_mode(mut mode: ResMut<OverlayMode>) {
+    *mode = OverlayMode::Off;
+}
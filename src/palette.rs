@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+
+use crate::{debug::TEXT_SIZE, person::Person, states::GameState};
+
+/// How much of the palette colour goes into a tinted sprite, the rest staying white.
+const TINT_STRENGTH: f32 = 0.6;
+/// The radius of the ring drawn under people, in the ring tint mode.
+const RING_RADIUS: f32 = 7.0;
+
+/// The colours good and bad things are shown with, for colour vision deficiencies too.
+/// Kept between games, as a setting.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub enum Palette {
+    /// Green for good, red for bad
+    #[default]
+    Standard,
+    /// Blue for good, vermillion for bad, readable with deuteranopia and protanopia
+    Deuteranopia,
+    /// Teal for good, red for bad, readable with tritanopia
+    Tritanopia,
+}
+
+impl Palette {
+    fn next(self) -> Self {
+        match self {
+            Palette::Standard => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Tritanopia,
+            Palette::Tritanopia => Palette::Standard,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Palette::Standard => "Standard",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::Tritanopia => "Tritanopia",
+        }
+    }
+
+    pub fn good(self) -> Color {
+        match self {
+            Palette::Standard => Color::GREEN,
+            Palette::Deuteranopia => Color::rgb_u8(0, 114, 178),
+            Palette::Tritanopia => Color::rgb_u8(0, 158, 150),
+        }
+    }
+
+    /// Between good and bad
+    pub fn warning(self) -> Color {
+        match self {
+            Palette::Standard => Color::ORANGE,
+            Palette::Deuteranopia => Color::rgb_u8(240, 228, 66),
+            Palette::Tritanopia => Color::rgb_u8(240, 160, 180),
+        }
+    }
+
+    pub fn bad(self) -> Color {
+        match self {
+            Palette::Standard => Color::RED,
+            Palette::Deuteranopia => Color::rgb_u8(213, 94, 0),
+            Palette::Tritanopia => Color::rgb_u8(220, 40, 40),
+        }
+    }
+
+    /// The colour of a ratio from 0 (bad) to 1 (good), through the warning colour
+    pub fn gradient(self, ratio: f32) -> Color {
+        let ratio = ratio.clamp(0.0, 1.0);
+        match ratio < 0.5 {
+            true => mix(self.bad(), self.warning(), ratio * 2.0),
+            false => mix(self.warning(), self.good(), ratio * 2.0 - 1.0),
+        }
+    }
+}
+
+/// Blend two colours, from all of `from` at 0 to all of `to` at 1
+pub fn mix(from: Color, to: Color, ratio: f32) -> Color {
+    let from = Vec4::from(from.as_rgba_f32());
+    let to = Vec4::from(to.as_rgba_f32());
+    Color::from(from.lerp(to, ratio))
+}
+
+/// How people show their satisfaction, if at all.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub enum TintMode {
+    #[default]
+    Off,
+    /// Tint the whole sprite
+    Sprite,
+    /// Draw a ring under their feet
+    Ring,
+}
+
+impl TintMode {
+    fn next(self) -> Self {
+        match self {
+            TintMode::Off => TintMode::Sprite,
+            TintMode::Sprite => TintMode::Ring,
+            TintMode::Ring => TintMode::Off,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TintMode::Off => "Off",
+            TintMode::Sprite => "Sprite",
+            TintMode::Ring => "Ring",
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct PaletteText;
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Palette>()
+            .init_resource::<TintMode>()
+            .add_systems(OnEnter(GameState::Playing), spawn_palette_text)
+            .add_systems(
+                Update,
+                (switch_palette, tint_persons, update_palette_text)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_palette_text);
+    }
+}
+
+/// G cycles through tint modes, K through palettes
+fn switch_palette(
+    keys: Res<Input<KeyCode>>,
+    mut palette: ResMut<Palette>,
+    mut tint_mode: ResMut<TintMode>,
+) {
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::G) {
+        *tint_mode = tint_mode.next();
+    }
+    if keys.just_pressed(KeyCode::K) {
+        *palette = palette.next();
+    }
+}
+
+/// Colour people by their satisfaction, as the tint mode says
+fn tint_persons(
+    palette: Res<Palette>,
+    tint_mode: Res<TintMode>,
+    mut persons_query: Query<(&Person, &Transform, &mut TextureAtlasSprite)>,
+    mut gizmos: Gizmos,
+) {
+    for (person, transform, mut sprite) in &mut persons_query {
+        let tint = palette.gradient(person.satisfaction / 100.0);
+        sprite.color = match *tint_mode {
+            TintMode::Sprite => mix(Color::WHITE, tint, TINT_STRENGTH),
+            TintMode::Off | TintMode::Ring => Color::WHITE,
+        };
+        if *tint_mode == TintMode::Ring {
+            let feet = transform.translation.truncate() - Vec2::new(0.0, RING_RADIUS);
+            gizmos.circle_2d(feet, RING_RADIUS, tint);
+        }
+    }
+}
+
+fn spawn_palette_text(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: TEXT_SIZE - 6.0,
+        ..default()
+    };
+    let value_style = TextStyle {
+        color: Color::GOLD,
+        ..text_style.clone()
+    };
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new("Tint (G): ", text_style.clone()),
+            TextSection::from_style(value_style.clone()),
+            TextSection::new("  Palette (K): ", text_style),
+            TextSection::from_style(value_style),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            // Above the tools
+            left: Val::VMax(1.0),
+            bottom: Val::VMin(16.0),
+            ..default()
+        }),
+        PaletteText,
+    ));
+}
+
+fn update_palette_text(
+    palette: Res<Palette>,
+    tint_mode: Res<TintMode>,
+    mut text_query: Query<&mut Text, With<PaletteText>>,
+) {
+    let mut text = text_query.single_mut();
+    text.sections[1].value = tint_mode.label().to_string();
+    text.sections[3].value = palette.label().to_string();
+}
+
+fn cleanup_palette_text(mut commands: Commands, text_query: Query<Entity, With<PaletteText>>) {
+    for entity in &text_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,31 +1,60 @@
 use bevy::prelude::*;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
 
 use crate::{
-    asset_loader::AssetHandles, building::BuildingType, drag::Interactable, movement::MovementDir,
+    animation::Animation,
+    asset_loader::AssetHandles,
+    beauty::{beauty, decoration_weight},
+    building::BuildingType,
+    daynight::{is_lit, tick_clock, Clock},
+    depth::{YSorted, STANDING_LEVEL},
+    drag::Interactable,
+    events::Burning,
+    ground::{to_world_pos, Ground},
+    mapgen::{build_map, MapSettings},
+    movement::{resolve_movements, MovementDir},
+    notification::{Category, Notification},
+    scenario::start_scenario,
     states::GameState,
+    unlock::check_milestones,
 };
 
 pub const SPRITE_SCALE: Vec3 = Vec3::new(1.0, 1.0, 0.0);
-const SPRITE_SIZE: Vec3 = Vec3::new(16.0, 23.0, 0.0);
+pub const SPRITE_SIZE: Vec3 = Vec3::new(16.0, 23.0, 0.0);
 
-// for z-ordering
-const PERSON_LEVEL: f32 = 2.0;
+/// The default time between two arrivals, in seconds.
+pub const BASE_SPAWN_DELAY: f32 = 6.3;
 
 /// The distance below which a building applies its effect on a person.
-const INTERACTION_DISTANCE: f32 = 20.0;
+pub const INTERACTION_DISTANCE: f32 = 20.0;
 
 #[derive(Resource)]
 pub struct UsedPersons {
     pub list: Vec<i32>,
 }
 
+/// Time between two arrivals in the city.
 #[derive(Resource, Debug)]
-struct SpawnTimer(Timer);
+pub struct SpawnTimer(pub Timer);
 
 #[derive(Resource, Debug)]
 struct ScoreUpdateTimer(Timer);
 
+/// The random generator of everything that shapes the simulation, seeded
+/// from the map seed when a game starts so that games can be replayed.
+/// Looks, like which character a person is drawn as, don't use it.
+/// Draw from it going through people by id: queries visit them in an order
+/// that depends on what components they gained along the way, like bounds
+/// once their image is loaded.
+#[derive(Resource, Debug)]
+pub struct SimRng(pub StdRng);
+
+impl Default for SimRng {
+    fn default() -> Self {
+        SimRng(StdRng::seed_from_u64(0))
+    }
+}
+
 #[derive(Component, Debug, Default, Clone)]
 pub struct Person {
     pub id: i32,
@@ -37,6 +66,8 @@ pub struct Person {
     pub sport: f32,
     pub creativity: f32,
     pub satisfaction: f32,
+    /// What the decorations around add to satisfaction, or take away from it
+    pub beauty: f32,
     // Idle movt direction
     pub movement_direction: MovementDir,
     pub movement_vector: Vec2,
@@ -48,7 +79,9 @@ pub struct Person {
 #[derive(Bundle)]
 struct PersonBundle {
     person: Person,
-    sprite: SpriteBundle,
+    sprite: SpriteSheetBundle,
+    animation: Animation,
+    y_sorted: YSorted,
 }
 
 pub struct PersonPlugin;
@@ -56,12 +89,22 @@ pub struct PersonPlugin;
 impl Plugin for PersonPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(UsedPersons { list: vec![] })
-            .insert_resource(SpawnTimer(Timer::from_seconds(6.3, TimerMode::Repeating)))
+            .insert_resource(SpawnTimer(Timer::from_seconds(
+                BASE_SPAWN_DELAY,
+                TimerMode::Repeating,
+            )))
             .insert_resource(ScoreUpdateTimer(Timer::from_seconds(
                 1.0,
                 TimerMode::Repeating,
             )))
-            .add_systems(OnEnter(GameState::Playing), spawn_first_person)
+            .init_resource::<SimRng>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (start_simulation, spawn_first_person)
+                    .chain()
+                    .after(build_map)
+                    .after(start_scenario),
+            )
             .add_systems(
                 Update,
                 (
@@ -70,63 +113,84 @@ impl Plugin for PersonPlugin {
                     increase_scores,
                     hitbox_follow,
                 )
+                    .chain()
+                    .after(tick_clock)
+                    .after(check_milestones)
                     .run_if(in_state(GameState::Playing)),
             )
             .add_systems(
                 PostUpdate,
-                update_liked_disliked.run_if(in_state(GameState::Playing)),
+                update_liked_disliked
+                    .after(resolve_movements)
+                    .run_if(in_state(GameState::Playing)),
             )
             .add_systems(OnExit(GameState::Playing), cleanup_persons);
     }
 }
 
+/// Seed the simulation, and restart the timers a previous game left running
+fn start_simulation(
+    mut sim_rng: ResMut<SimRng>,
+    mut update_timer: ResMut<ScoreUpdateTimer>,
+    settings: Res<MapSettings>,
+) {
+    sim_rng.0 = StdRng::seed_from_u64(settings.seed);
+    update_timer.0.reset();
+}
+
 fn spawn_first_person(
     mut commands: Commands,
     asset_handles: Res<AssetHandles>,
     mut used_ids: ResMut<UsedPersons>,
+    ground: Res<Ground>,
+    mut sim_rng: ResMut<SimRng>,
 ) {
-    commands.spawn(PersonBundle {
-        person: Person {
-            id: 0,
-            shelter: 10.0,
-            hunger: 50.0,
-            social: 75.0,
-            entertained: 100.0,
-            health: 100.0,
-            sport: 100.0,
-            creativity: 100.0,
-            satisfaction: 50.0,
-            movement_direction: MovementDir::PlusBoth,
-            movement_vector: Vec2::ZERO,
-            ..default()
-        },
-        sprite: SpriteBundle {
-            texture: asset_handles.person.clone(),
-            transform: Transform {
-                scale: SPRITE_SCALE,
-                translation: Vec3::new(0.0, 0.0, PERSON_LEVEL),
-                ..default()
-            },
-            ..default()
-        },
-    });
-    used_ids.list.push(0);
+    spawn_new_person(
+        arrival_position(&ground, &mut sim_rng.0),
+        &mut commands,
+        &asset_handles,
+        &mut used_ids,
+    );
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_person(
     mut commands: Commands,
     asset_handles: Res<AssetHandles>,
     mut used_ids: ResMut<UsedPersons>,
     time: Res<Time>,
     mut spawn_timer: ResMut<SpawnTimer>,
+    ground: Res<Ground>,
+    mut sim_rng: ResMut<SimRng>,
+    mut notifications: EventWriter<Notification>,
 ) {
     if spawn_timer.0.tick(time.delta()).just_finished() && used_ids.list.len() <= 2000 {
-        let largest_id = used_ids.list.iter().max();
-        let mut available_id = 0;
-        if let Some(val) = largest_id {
-            available_id = val + 1;
-        }
-        commands.spawn(PersonBundle {
+        let person = spawn_new_person(
+            arrival_position(&ground, &mut sim_rng.0),
+            &mut commands,
+            &asset_handles,
+            &mut used_ids,
+        );
+        notifications.send(
+            Notification::new(Category::Arrival, "A newcomer arrived in the city").about(person),
+        );
+    }
+}
+
+/// Spawn a person with a fresh id at the given position
+pub fn spawn_new_person(
+    position: Vec2,
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+    used_ids: &mut ResMut<UsedPersons>,
+) -> Entity {
+    let largest_id = used_ids.list.iter().max();
+    let mut available_id = 0;
+    if let Some(val) = largest_id {
+        available_id = val + 1;
+    }
+    let entity = commands
+        .spawn(PersonBundle {
             person: Person {
                 id: available_id,
                 shelter: 10.0,
@@ -141,25 +205,49 @@ fn spawn_person(
                 movement_vector: Vec2::ZERO,
                 ..default()
             },
-            sprite: SpriteBundle {
-                texture: asset_handles.person.clone(),
+            sprite: SpriteSheetBundle {
+                // Each arrival looks like one of the characters
+                texture_atlas: asset_handles
+                    .persons
+                    .choose(&mut thread_rng())
+                    .cloned()
+                    .unwrap_or_default(),
                 transform: Transform {
                     scale: SPRITE_SCALE,
-                    translation: Vec3::new(0.0, 0.0, PERSON_LEVEL),
+                    translation: position.extend(STANDING_LEVEL),
                     ..default()
                 },
                 ..default()
             },
-        });
-        used_ids.list.push(available_id);
-    }
+            animation: Animation::default(),
+            y_sorted: YSorted {
+                base: SPRITE_SIZE.y / 2.0,
+            },
+        })
+        .id();
+    used_ids.list.push(available_id);
+    entity
+}
+
+/// Where a new person appears: one of the map's entry points, or the origin
+fn arrival_position(ground: &Ground, rng: &mut StdRng) -> Vec2 {
+    ground
+        .entry_points
+        .choose(rng)
+        .map_or(Vec2::ZERO, |entry| to_world_pos(*entry))
 }
 
-fn update_liked_disliked(mut person_query: Query<&mut Person>, used_ids: Res<UsedPersons>) {
-    for mut person in &mut person_query {
+pub fn update_liked_disliked(
+    mut person_query: Query<&mut Person>,
+    used_ids: Res<UsedPersons>,
+    mut sim_rng: ResMut<SimRng>,
+) {
+    let mut persons: Vec<Mut<Person>> = person_query.iter_mut().collect();
+    persons.sort_by_key(|person| person.id);
+    for mut person in persons {
         for id in &used_ids.list {
             if !person.liked.contains(id) && !person.disliked.contains(id) {
-                let likes_this_one: bool = rand::random();
+                let likes_this_one: bool = sim_rng.0.gen();
                 if likes_this_one {
                     person.liked.push(*id);
                 } else {
@@ -171,24 +259,53 @@ fn update_liked_disliked(mut person_query: Query<&mut Person>, used_ids: Res<Use
 }
 
 fn decrease_scores(
-    mut persons_query: Query<&mut Person>,
+    mut persons_query: Query<(Entity, &mut Person, &Transform)>,
+    buildings_query: Query<(&BuildingType, &Transform)>,
     time: Res<Time>,
+    clock: Res<Clock>,
     mut update_timer: ResMut<ScoreUpdateTimer>,
+    mut sim_rng: ResMut<SimRng>,
+    mut notifications: EventWriter<Notification>,
 ) {
     if update_timer.0.tick(time.delta()).just_finished() {
-        let mut rng = thread_rng();
-        for mut person in &mut persons_query {
-            person.shelter = clamp_score(person.shelter - 1.0);
+        let rng = &mut sim_rng.0;
+        let phase = clock.phase();
+        let positions_of = |wanted: BuildingType| -> Vec<Vec3> {
+            buildings_query
+                .iter()
+                .filter(|(b_type, _)| **b_type == wanted)
+                .map(|(_, transform)| transform.translation)
+                .collect()
+        };
+        let lamps = positions_of(BuildingType::Lamp);
+        let decorations: Vec<(BuildingType, Vec2)> = buildings_query
+            .iter()
+            .filter(|(b_type, _)| decoration_weight(**b_type) > 0.0)
+            .map(|(b_type, transform)| (*b_type, transform.translation.truncate()))
+            .collect();
+
+        let mut persons: Vec<_> = persons_query.iter_mut().collect();
+        persons.sort_by_key(|(_, person, _)| person.id);
+        for (entity, mut person, transform) in persons {
+            let lit = is_lit(transform.translation, &lamps);
+            person.shelter = clamp_score(person.shelter - phase.shelter_decay(lit));
             person.hunger = clamp_score(person.hunger - 0.75);
 
             let needs_sport = rng.gen_bool(0.7);
             let needs_creation = rng.gen_bool(0.68);
-            let needs_entertainment = rng.gen_bool(0.45);
+            let needs_entertainment = rng.gen_bool(phase.boredom_chance());
             let needs_social = rng.gen_bool(0.4);
             let health_incident = rng.gen_bool(0.005);
 
             if health_incident {
                 person.health = clamp_score(person.health - 75.0);
+                notifications.send(
+                    Notification::new(
+                        Category::Health,
+                        format!("Person {}'s health crashed", person.id),
+                    )
+                    .about(entity),
+                );
             }
             if needs_entertainment {
                 person.entertained = clamp_score(person.entertained - 1.0);
@@ -203,21 +320,25 @@ fn decrease_scores(
                 person.social = clamp_score(person.social - 1.0);
             }
 
-            person.satisfaction = (person.shelter
-                + person.hunger
-                + person.health
-                + person.entertained
-                + person.sport
-                + person.creativity
-                + person.social)
-                / 7.0;
+            person.beauty = beauty(transform.translation.truncate(), &decorations);
+            person.satisfaction = clamp_score(
+                (person.shelter
+                    + person.hunger
+                    + person.health
+                    + person.entertained
+                    + person.sport
+                    + person.creativity
+                    + person.social)
+                    / 7.0
+                    + person.beauty,
+            );
         }
     }
 }
 
 fn increase_scores(
     mut persons_query: Query<(&mut Person, &Transform)>,
-    buildings_query: Query<(&BuildingType, &Transform)>,
+    buildings_query: Query<(&BuildingType, &Transform), Without<Burning>>,
     time: Res<Time>,
 ) {
     for (mut person, p_transform) in &mut persons_query {
@@ -252,11 +373,11 @@ fn increase_scores(
     }
 }
 
-fn clamp_score(val: f32) -> f32 {
+pub fn clamp_score(val: f32) -> f32 {
     val.clamp(0.0, 100.0)
 }
 
-fn hitbox_follow(mut persons_query: Query<(&mut Person, &Transform)>) {
+pub fn hitbox_follow(mut persons_query: Query<(&mut Person, &Transform)>) {
     for (mut person, transform) in &mut persons_query {
         person.interact.bottom_left.x = transform.translation.x - SPRITE_SIZE.x / 2.0;
         person.interact.bottom_left.y = transform.translation.y - SPRITE_SIZE.y / 2.0;
@@ -275,3 +396,78 @@ fn cleanup_persons(
     }
     used_ids.list = vec![];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{test_app, TEST_TICK};
+
+    /// How much hunger a restaurant satisfies per second.
+    const RESTAURANT_RATE: f32 = 50.0;
+
+    fn hunger_after_a_tick(restaurant_distance: f32) -> (f32, f32) {
+        let mut app = test_app();
+        app.add_systems(Update, increase_scores);
+        let person = app
+            .world
+            .spawn((
+                Person {
+                    hunger: 10.0,
+                    ..default()
+                },
+                Transform::default(),
+            ))
+            .id();
+        app.world.spawn((
+            BuildingType::Restaurant,
+            Transform::from_xyz(restaurant_distance, 0.0, 0.0),
+        ));
+
+        let before = app.world.get::<Person>(person).unwrap().hunger;
+        app.update();
+        let after = app.world.get::<Person>(person).unwrap().hunger;
+        (before, after)
+    }
+
+    #[test]
+    fn clamp_score_keeps_scores_within_bounds() {
+        assert_eq!(clamp_score(-25.0), 0.0);
+        assert_eq!(clamp_score(0.0), 0.0);
+        assert_eq!(clamp_score(42.5), 42.5);
+        assert_eq!(clamp_score(100.0), 100.0);
+        assert_eq!(clamp_score(180.0), 100.0);
+    }
+
+    #[test]
+    fn restaurant_feeds_nearby_person() {
+        let (before, after) = hunger_after_a_tick(INTERACTION_DISTANCE / 2.0);
+        assert!((after - before - RESTAURANT_RATE * TEST_TICK).abs() < 1e-3);
+    }
+
+    #[test]
+    fn restaurant_does_not_feed_faraway_person() {
+        let (before, after) = hunger_after_a_tick(INTERACTION_DISTANCE * 2.0);
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn burning_restaurant_does_not_feed() {
+        let mut app = test_app();
+        app.add_systems(Update, increase_scores);
+        let person = app
+            .world
+            .spawn((
+                Person {
+                    hunger: 10.0,
+                    ..default()
+                },
+                Transform::default(),
+            ))
+            .id();
+        app.world
+            .spawn((BuildingType::Restaurant, Transform::default(), Burning));
+
+        app.update();
+        assert_eq!(app.world.get::<Person>(person).unwrap().hunger, 10.0);
+    }
+}
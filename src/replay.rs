@@ -0,0 +1,626 @@
+use std::{hash::Hash, time::Duration};
+
+use bevy::{
+    ecs::event::ManualEventReader, prelude::*, time::TimeUpdateStrategy, window::ReceivedCharacter,
+};
+
+use crate::{
+    blueprint::{Blueprint, BlueprintPart, Blueprints},
+    building::BuildingType,
+    camera::CursorPosition,
+    mapgen::{MapSettings, MapSource},
+    minimap::click_minimap,
+    notification::{Category, Notification},
+    scenario::{ActiveScenario, Scenarios},
+    states::GameState,
+};
+
+/// Where replays are saved.
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAYS_DIR: &str = "replays";
+/// The name the replay of the last game is saved under, once it ends.
+const LAST_REPLAY: &str = "last";
+const REPLAY_VERSION: u32 = 1;
+
+const REPLAYED_BUTTONS: [MouseButton; 2] = [MouseButton::Left, MouseButton::Right];
+/// The keys the game reacts to while playing. A new shortcut must be added
+/// here, or replays will not know about it.
+const REPLAYED_KEYS: [KeyCode; 37] = [
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Tab,
+    KeyCode::Return,
+    KeyCode::Delete,
+    KeyCode::Escape,
+    KeyCode::Back,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+];
+
+/// A key or mouse button.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Mouse(MouseButton),
+    Key(KeyCode),
+}
+
+impl Control {
+    /// Mouse buttons are prefixed, as some keys share their names
+    fn name(self) -> String {
+        match self {
+            Control::Mouse(button) => format!("Mouse{:?}", button),
+            Control::Key(key) => format!("{:?}", key),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let buttons = REPLAYED_BUTTONS.map(Control::Mouse);
+        let keys = REPLAYED_KEYS.map(Control::Key);
+        buttons
+            .into_iter()
+            .chain(keys)
+            .find(|control| control.name() == name)
+    }
+}
+
+/// The state of a control during a tick, as the world saw it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Press {
+    pub pressed: bool,
+    pub just_pressed: bool,
+    pub just_released: bool,
+}
+
+impl Press {
+    fn of<T: Copy + Eq + Hash + Send + Sync + 'static>(input: &Input<T>, control: T) -> Self {
+        Press {
+            pressed: input.pressed(control),
+            just_pressed: input.just_pressed(control),
+            just_released: input.just_released(control),
+        }
+    }
+
+    /// Put the control in this state, whatever it was in
+    fn apply<T: Copy + Eq + Hash + Send + Sync + 'static>(self, input: &mut Input<T>, control: T) {
+        input.reset(control);
+        if self.pressed || self.just_pressed || self.just_released {
+            input.press(control);
+        }
+        if !self.just_pressed {
+            input.clear_just_pressed(control);
+        }
+        if self.just_released || !self.pressed {
+            input.release(control);
+        }
+        if !self.just_released {
+            input.clear_just_released(control);
+        }
+    }
+
+    /// Written after the control's name: `+` when just pressed, `-` when just released
+    fn suffix(self) -> &'static str {
+        match (self.just_pressed, self.just_released) {
+            (true, true) => "+-",
+            (true, false) => "+",
+            (false, true) => "-",
+            (false, false) => "",
+        }
+    }
+}
+
+/// What the world saw of the player during one tick.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TickInput {
+    /// How long the frame lasted
+    pub delta: Duration,
+    /// Where the cursor moved to, in world coordinates, if it moved
+    pub cursor: Option<Vec2>,
+    /// The controls that were not at rest
+    pub controls: Vec<(Control, Press)>,
+    /// The characters typed, for text prompts
+    pub typed: String,
+}
+
+/// A game, as what it takes to play it again: the map, the scenario,
+/// the blueprints at hand, and the inputs of every tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub map: MapSettings,
+    /// The name of the scenario played, or `None` in sandbox mode
+    pub scenario: Option<String>,
+    /// The blueprints the player could paste when the game started, as the
+    /// saved ones may have changed since
+    pub blueprints: Vec<Blueprint>,
+    pub ticks: Vec<TickInput>,
+}
+
+impl Replay {
+    fn new(map: MapSettings, scenario: Option<String>, blueprints: Vec<Blueprint>) -> Self {
+        Replay {
+            map,
+            scenario,
+            blueprints,
+            ticks: vec![],
+        }
+    }
+
+    /// Read a replay file. It starts with these lines:
+    ///
+    /// - `replay <version>`
+    /// - `map handmade <seed>` or `map generated <seed> [no-starters]`
+    /// - `scenario <name>` or `sandbox`
+    /// - `blueprint <name>` for each blueprint, followed by a
+    ///   `part <type> <x> <y> <quarter turns>` line per building
+    ///
+    /// Then comes a line per tick: `<tick> <nanoseconds> [@<x>,<y>] <controls>... ["<typed>]`,
+    /// each control being its name followed by `+` if just pressed, `-` if just
+    /// released, and listed only while pressed or just released
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut replay = Replay::new(MapSettings::default(), None, vec![]);
+        let mut lines = text.lines().enumerate();
+
+        match lines.next() {
+            Some((_, line)) if line == format!("replay {}", REPLAY_VERSION) => {}
+            _ => return Err(format!("Not a version {} replay", REPLAY_VERSION)),
+        }
+        for (line_nb, line) in lines {
+            let error = || format!("Invalid replay line {}: {}", line_nb + 1, line);
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let words: Vec<&str> = rest.split_whitespace().collect();
+
+            match (keyword, &words[..]) {
+                ("map", [source, seed, options @ ..]) => {
+                    replay.map.source = match *source {
+                        "handmade" => MapSource::Handmade,
+                        "generated" => MapSource::Generated,
+                        _ => return Err(error()),
+                    };
+                    replay.map.seed = seed.parse().map_err(|_| error())?;
+                    replay.map.starter_buildings = !options.contains(&"no-starters");
+                }
+                ("scenario", _) => replay.scenario = Some(rest.trim().to_string()),
+                ("sandbox", []) => replay.scenario = None,
+                ("blueprint", _) => replay.blueprints.push(Blueprint {
+                    name: rest.trim().to_string(),
+                    parts: vec![],
+                }),
+                ("part", [b_type, x_pos, y_pos, turns]) => {
+                    let blueprint = replay.blueprints.last_mut().ok_or_else(error)?;
+                    blueprint.parts.push(BlueprintPart {
+                        b_type: BuildingType::from_id(b_type).ok_or_else(error)?,
+                        offset: Vec2::new(
+                            x_pos.parse().map_err(|_| error())?,
+                            y_pos.parse().map_err(|_| error())?,
+                        ),
+                        quarter_turns: turns.parse().map_err(|_| error())?,
+                    });
+                }
+                (tick, [nanos, inputs @ ..]) => {
+                    if tick.parse() != Ok(replay.ticks.len()) {
+                        return Err(error());
+                    }
+                    let mut tick_input = TickInput {
+                        delta: Duration::from_nanos(nanos.parse().map_err(|_| error())?),
+                        ..default()
+                    };
+                    for input in inputs {
+                        if let Some(typed) = input.strip_prefix('"') {
+                            tick_input.typed = unescape_typed(typed).ok_or_else(error)?;
+                            continue;
+                        }
+                        if let Some(position) = input.strip_prefix('@') {
+                            let (x_pos, y_pos) = position.split_once(',').ok_or_else(error)?;
+                            tick_input.cursor = Some(Vec2::new(
+                                x_pos.parse().map_err(|_| error())?,
+                                y_pos.parse().map_err(|_| error())?,
+                            ));
+                            continue;
+                        }
+                        let name = input.trim_end_matches(['+', '-']);
+                        let suffix = &input[name.len()..];
+                        let press = Press {
+                            pressed: suffix.is_empty() || suffix == "+",
+                            just_pressed: suffix.starts_with('+'),
+                            just_released: suffix.ends_with('-'),
+                        };
+                        let control = Control::from_name(name).ok_or_else(error)?;
+                        tick_input.controls.push((control, press));
+                    }
+                    replay.ticks.push(tick_input);
+                }
+                _ => return Err(error()),
+            }
+        }
+        Ok(replay)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("replay {}\n", REPLAY_VERSION);
+        text.push_str(&match self.map.source {
+            MapSource::Handmade => format!("map handmade {}\n", self.map.seed),
+            MapSource::Generated => format!(
+                "map generated {}{}\n",
+                self.map.seed,
+                match self.map.starter_buildings {
+                    true => "",
+                    false => " no-starters",
+                }
+            ),
+        });
+        text.push_str(&match &self.scenario {
+            Some(name) => format!("scenario {}\n", name),
+            None => "sandbox\n".to_string(),
+        });
+        for blueprint in &self.blueprints {
+            text.push_str(&format!("blueprint {}\n", blueprint.name));
+            for part in &blueprint.parts {
+                text.push_str(&format!(
+                    "part {} {} {} {}\n",
+                    part.b_type.id(),
+                    part.offset.x,
+                    part.offset.y,
+                    part.quarter_turns
+                ));
+            }
+        }
+        for (tick, input) in self.ticks.iter().enumerate() {
+            text.push_str(&format!("{} {}", tick, input.delta.as_nanos()));
+            if let Some(cursor) = input.cursor {
+                text.push_str(&format!(" @{},{}", cursor.x, cursor.y));
+            }
+            for (control, press) in &input.controls {
+                text.push_str(&format!(" {}{}", control.name(), press.suffix()));
+            }
+            if !input.typed.is_empty() {
+                text.push_str(&format!(" \"{}", escape_typed(&input.typed)));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Typed text as a single word, other characters than letters, digits and
+/// punctuation being escaped like `\u{20}`
+fn escape_typed(typed: &str) -> String {
+    let kept = |c: char| c.is_alphanumeric() || (c.is_ascii_graphic() && c != '\\');
+    typed
+        .chars()
+        .map(|c| match kept(c) {
+            true => c.to_string(),
+            false => c.escape_unicode().to_string(),
+        })
+        .collect()
+}
+
+fn unescape_typed(word: &str) -> Option<String> {
+    let mut typed = String::new();
+    let mut rest = word;
+    while let Some(c) = rest.chars().next() {
+        match rest.strip_prefix("\\u{") {
+            Some(escaped) => {
+                let (code, after) = escaped.split_once('}')?;
+                typed.push(char::from_u32(u32::from_str_radix(code, 16).ok()?)?);
+                rest = after;
+            }
+            None => {
+                typed.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    Some(typed)
+}
+
+/// The replay of the game being played, recorded as it goes.
+#[derive(Resource, Debug, Default)]
+pub struct Recorder {
+    replay: Option<Replay>,
+    last_cursor: Option<Vec2>,
+}
+
+/// The replay being played back, and how far along it is.
+#[derive(Resource, Debug, Default)]
+pub struct Playback {
+    replay: Option<Replay>,
+    tick: usize,
+    cursor: Vec2,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback {
+            replay: Some(replay),
+            ..default()
+        }
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recorder>()
+            .init_resource::<Playback>()
+            .add_systems(Startup, load_replay_argument)
+            .add_systems(PreUpdate, replay_inputs.after(click_minimap))
+            .add_systems(Update, save_replay.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// `--replay <file>` plays the given replay back when the game opens,
+/// starting the game it was recorded in
+#[cfg(not(target_arch = "wasm32"))]
+fn load_replay_argument(
+    mut playback: ResMut<Playback>,
+    mut map_settings: ResMut<MapSettings>,
+    mut active_scenario: ResMut<ActiveScenario>,
+    scenarios: Res<Scenarios>,
+    mut blueprints: ResMut<Blueprints>,
+    mut time_strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--replay")
+        .and_then(|i| args.get(i + 1))
+    else {
+        return;
+    };
+    match std::fs::read_to_string(path)
+        .map_err(|error| error.to_string())
+        .and_then(|text| Replay::parse(&text))
+    {
+        Ok(replay) => {
+            *map_settings = replay.map.clone();
+            active_scenario.0 = replay.scenario.as_ref().and_then(|name| {
+                scenarios
+                    .0
+                    .iter()
+                    .position(|scenario| scenario.name == *name)
+            });
+            blueprints.0 = replay.blueprints.clone();
+            // The first tick lasts as long as it did when recorded, like the next ones
+            *time_strategy = TimeUpdateStrategy::ManualDuration(
+                replay
+                    .ticks
+                    .first()
+                    .map_or(Duration::ZERO, |input| input.delta),
+            );
+            *playback = Playback::new(replay);
+        }
+        Err(error) => warn!("Could not load replay {}: {}", path, error),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_replay_argument() {}
+
+/// Returns `true` if the game systems run this frame, once states are updated
+fn playing_this_frame(state: &State<GameState>, next_state: &NextState<GameState>) -> bool {
+    *next_state.0.as_ref().unwrap_or(state.get()) == GameState::Playing
+}
+
+/// Play the inputs of the replay being played back, if any, then record what
+/// the world sees of the player, once UI elements have taken their clicks
+#[allow(clippy::too_many_arguments)]
+pub fn replay_inputs(
+    mut recorder: ResMut<Recorder>,
+    mut playback: ResMut<Playback>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    map_settings: Res<MapSettings>,
+    active_scenario: Res<ActiveScenario>,
+    scenarios: Res<Scenarios>,
+    blueprints: Res<Blueprints>,
+    mut time_strategy: ResMut<TimeUpdateStrategy>,
+    time: Res<Time>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut characters: ResMut<Events<ReceivedCharacter>>,
+    mut characters_read: Local<ManualEventReader<ReceivedCharacter>>,
+    mut cursor_pos: ResMut<CursorPosition>,
+    mut notifications: EventWriter<Notification>,
+) {
+    let playback = &mut *playback;
+    if let Some(replay) = &playback.replay {
+        match replay.ticks.get(playback.tick) {
+            Some(input) => {
+                if playback.tick == 0 {
+                    next_state.set(GameState::Playing);
+                    notifications.send(Notification::new(
+                        Category::Progress,
+                        format!("Playing back a replay of {} ticks", replay.ticks.len()),
+                    ));
+                }
+                keys.reset_all();
+                buttons.reset_all();
+                for (control, press) in &input.controls {
+                    match control {
+                        Control::Mouse(button) => press.apply(&mut buttons, *button),
+                        Control::Key(key) => press.apply(&mut keys, *key),
+                    }
+                }
+                // Only the characters of the replay get typed
+                characters.clear();
+                for char in input.typed.chars() {
+                    characters.send(ReceivedCharacter {
+                        window: Entity::PLACEHOLDER,
+                        char,
+                    });
+                }
+                playback.cursor = input.cursor.unwrap_or(playback.cursor);
+                cursor_pos.0 = playback.cursor;
+                playback.tick += 1;
+                *time_strategy = match replay.ticks.get(playback.tick) {
+                    Some(next) => TimeUpdateStrategy::ManualDuration(next.delta),
+                    None => TimeUpdateStrategy::Automatic,
+                };
+            }
+            None => {
+                // Hand the game back to the player, without the keys the replay held
+                keys.reset_all();
+                buttons.reset_all();
+                playback.replay = None;
+                notifications.send(Notification::new(Category::Progress, "Replay finished"));
+            }
+        }
+    }
+
+    let typed: String = characters_read
+        .read(&characters)
+        .map(|received| received.char)
+        .collect();
+    if !playing_this_frame(&state, &next_state) {
+        if let Some(replay) = recorder.replay.take() {
+            write_replay(&replay, LAST_REPLAY);
+        }
+        return;
+    }
+    let recorder = &mut *recorder;
+    let replay = recorder.replay.get_or_insert_with(|| {
+        let scenario = active_scenario
+            .0
+            .and_then(|i| scenarios.0.get(i))
+            .map(|scenario| scenario.name.clone());
+        Replay::new(map_settings.clone(), scenario, blueprints.0.clone())
+    });
+    if replay.ticks.is_empty() {
+        recorder.last_cursor = None;
+    }
+
+    let cursor = (recorder.last_cursor != Some(cursor_pos.0)).then_some(cursor_pos.0);
+    recorder.last_cursor = Some(cursor_pos.0);
+    let buttons =
+        REPLAYED_BUTTONS.map(|button| (Control::Mouse(button), Press::of(&buttons, button)));
+    let keys = REPLAYED_KEYS.map(|key| (Control::Key(key), Press::of(&keys, key)));
+    replay.ticks.push(TickInput {
+        delta: time.delta(),
+        cursor,
+        controls: buttons
+            .into_iter()
+            .chain(keys)
+            .filter(|(_, press)| *press != Press::default())
+            .collect(),
+        typed,
+    });
+}
+
+/// X saves the replay of the game so far, to attach it to a bug report
+fn save_replay(
+    keys: Res<Input<KeyCode>>,
+    recorder: Res<Recorder>,
+    mut notifications: EventWriter<Notification>,
+) {
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        || !keys.just_pressed(KeyCode::X)
+    {
+        return;
+    }
+    let Some(replay) = &recorder.replay else {
+        return;
+    };
+    let name = format!("replay_{}", replay_stamp());
+    let message = match write_replay(replay, &name) {
+        Some(path) => format!("Replay of {} ticks saved to {}", replay.ticks.len(), path),
+        None => "Could not save the replay".to_string(),
+    };
+    notifications.send(Notification::new(Category::Progress, message));
+}
+
+/// Write the replay under the given name, returning its path if it worked
+#[cfg(not(target_arch = "wasm32"))]
+fn write_replay(replay: &Replay, name: &str) -> Option<String> {
+    let path = std::path::Path::new(REPLAYS_DIR).join(format!("{}.replay", name));
+    let saved =
+        std::fs::create_dir_all(REPLAYS_DIR).and_then(|_| std::fs::write(&path, replay.to_text()));
+    match saved {
+        Ok(()) => Some(path.display().to_string()),
+        Err(error) => {
+            warn!("Could not save replay to {:?}: {}", path, error);
+            None
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_replay(_replay: &Replay, _name: &str) -> Option<String> {
+    None
+}
+
+/// Seconds since the Unix epoch, to tell saved replays apart
+#[cfg(not(target_arch = "wasm32"))]
+fn replay_stamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn replay_stamp() -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_text_round_trips() {
+        let mut replay = Replay::new(
+            MapSettings::default(),
+            Some("Tutorial".to_string()),
+            vec![Blueprint {
+                name: "Two houses".to_string(),
+                parts: vec![BlueprintPart {
+                    b_type: BuildingType::House,
+                    offset: Vec2::new(-32.5, 0.25),
+                    quarter_turns: 1,
+                }],
+            }],
+        );
+        replay.ticks.push(TickInput {
+            delta: Duration::from_millis(16),
+            cursor: Some(Vec2::new(10.0, -4.5)),
+            controls: vec![(
+                Control::Key(KeyCode::ControlLeft),
+                Press {
+                    pressed: true,
+                    just_pressed: true,
+                    just_released: false,
+                },
+            )],
+            typed: "My \\u{20} \"café\"\t".to_string(),
+        });
+        replay.ticks.push(TickInput::default());
+        assert_eq!(Replay::parse(&replay.to_text()), Ok(replay));
+    }
+}
@@ -0,0 +1,528 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    asset_loader::AssetHandles,
+    building::{
+        get_size_from_type, spawn_building, BuildingCooldowns, BuildingType, UnlockedBuildings,
+    },
+    debug::TEXT_SIZE,
+    economy::Treasury,
+    events::{EventKind, EventSettings, ScheduledEvent},
+    ground::to_world_pos,
+    mapgen::{build_map, MapSettings, MapSource},
+    movement::desire_movement,
+    person::{spawn_new_person, Person, SpawnTimer, UsedPersons, BASE_SPAWN_DELAY},
+    score::Score,
+    states::GameState,
+};
+
+/// The scenarios shipped with the game, in level-select order.
+const BUNDLED_SCENARIOS: [&str; 3] = [
+    include_str!("../assets/scenarios/first_steps.scenario"),
+    include_str!("../assets/scenarios/river_town.scenario"),
+    include_str!("../assets/scenarios/metropolis.scenario"),
+];
+
+/// Where the names of completed scenarios are kept between runs.
+#[cfg(not(target_arch = "wasm32"))]
+const PROGRESS_FILE: &str = "complexcity_progress.txt";
+
+/// A goal the player has to reach to complete a scenario.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Objective {
+    /// Keep at least `count` people above `satisfaction` for `seconds` in a row
+    Keep {
+        count: usize,
+        satisfaction: f32,
+        seconds: f32,
+    },
+    /// Have at least this many people in the city
+    Population(usize),
+    /// Keep the score above `value` for `seconds` in a row
+    Score { value: f32, seconds: f32 },
+}
+
+impl Objective {
+    pub fn description(&self) -> String {
+        match self {
+            Objective::Keep {
+                count,
+                satisfaction,
+                seconds,
+            } => format!(
+                "Keep {} people above {:.0} satisfaction for {:.0}s",
+                count, satisfaction, seconds
+            ),
+            Objective::Population(count) => format!("Reach {} people", count),
+            Objective::Score { value, seconds } => {
+                format!("Keep the score above {:.0} for {:.0}s", value, seconds)
+            }
+        }
+    }
+
+    /// How long the objective must hold before being reached, in seconds
+    pub fn duration(&self) -> f32 {
+        match self {
+            Objective::Keep { seconds, .. } | Objective::Score { seconds, .. } => *seconds,
+            Objective::Population(_) => 0.0,
+        }
+    }
+
+    /// Returns `true` if the objective's condition holds right now
+    pub fn holds(&self, persons_query: &Query<&Person>, population: usize, score: f32) -> bool {
+        match self {
+            Objective::Keep {
+                count,
+                satisfaction,
+                ..
+            } => {
+                persons_query
+                    .iter()
+                    .filter(|person| person.satisfaction > *satisfaction)
+                    .count()
+                    >= *count
+            }
+            Objective::Population(count) => population >= *count,
+            Objective::Score { value, .. } => score > *value,
+        }
+    }
+}
+
+/// A handcrafted level: where it happens, what the player starts with, and what to achieve.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub description: String,
+    pub map: MapSettings,
+    /// Buildings placed before the game starts, on tile coordinates
+    pub buildings: Vec<(BuildingType, IVec2)>,
+    /// Groups of people placed before the game starts, on tile coordinates
+    pub people: Vec<(usize, IVec2)>,
+    /// The buildings the player may spawn, or `None` for all of them
+    pub unlocked: Option<Vec<BuildingType>>,
+    /// Cooldowns replacing the default ones, in seconds
+    pub cooldowns: Vec<(BuildingType, f32)>,
+    /// The time between two arrivals, in seconds
+    pub spawn_delay: Option<f32>,
+    /// The money the city starts with, replacing the default amount
+    pub funds: Option<f32>,
+    pub events: EventSettings,
+    pub objectives: Vec<Objective>,
+}
+
+impl Scenario {
+    /// Read a scenario file. Each line starts with a keyword:
+    ///
+    /// - `name <text>` and `description <text>`
+    /// - `map handmade` or `map generated <seed> [no-starters]`
+    /// - `building <type> <x> <y>`, placed on the given tile
+    /// - `people <count> <x> <y>`, placed on the given tile
+    /// - `unlock <type> <type>...`, all types being unlocked if missing
+    /// - `cooldown <type> <seconds>`
+    /// - `spawn_delay <seconds>`
+    /// - `funds <amount>`
+    /// - `events off` or `events random <seed> <mean interval>`, for random events
+    /// - `event <kind> <start> <duration>`, in seconds since the game started
+    /// - `objective keep <count> <satisfaction> <seconds>`,
+    ///   `objective population <count>` or `objective score <value> <seconds>`
+    ///
+    /// `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scenario = Scenario {
+            name: String::new(),
+            description: String::new(),
+            map: MapSettings::default(),
+            buildings: vec![],
+            people: vec![],
+            unlocked: None,
+            cooldowns: vec![],
+            spawn_delay: None,
+            funds: None,
+            events: EventSettings::default(),
+            objectives: vec![],
+        };
+
+        for (line_nb, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = || format!("Invalid scenario line {}: {}", line_nb + 1, line);
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let words: Vec<&str> = rest.split_whitespace().collect();
+
+            match (keyword, &words[..]) {
+                ("name", _) => scenario.name = rest.trim().to_string(),
+                ("description", _) => scenario.description = rest.trim().to_string(),
+                ("map", ["handmade"]) => scenario.map.source = MapSource::Handmade,
+                ("map", ["generated", seed, options @ ..]) => {
+                    scenario.map.source = MapSource::Generated;
+                    scenario.map.seed = seed.parse().map_err(|_| error())?;
+                    scenario.map.starter_buildings = !options.contains(&"no-starters");
+                }
+                ("building", [b_type, x_pos, y_pos]) => scenario.buildings.push((
+                    BuildingType::from_id(b_type).ok_or_else(error)?,
+                    parse_tile(x_pos, y_pos).ok_or_else(error)?,
+                )),
+                ("people", [count, x_pos, y_pos]) => scenario.people.push((
+                    count.parse().map_err(|_| error())?,
+                    parse_tile(x_pos, y_pos).ok_or_else(error)?,
+                )),
+                ("unlock", b_types) => {
+                    let b_types = b_types
+                        .iter()
+                        .map(|id| BuildingType::from_id(id).ok_or_else(error))
+                        .collect::<Result<_, _>>()?;
+                    scenario.unlocked = Some(b_types);
+                }
+                ("cooldown", [b_type, seconds]) => scenario.cooldowns.push((
+                    BuildingType::from_id(b_type).ok_or_else(error)?,
+                    parse_seconds(seconds)
+                        .filter(|seconds| *seconds > 0.0)
+                        .ok_or_else(error)?,
+                )),
+                ("spawn_delay", [seconds]) => {
+                    scenario.spawn_delay = Some(
+                        parse_seconds(seconds)
+                            .filter(|seconds| *seconds > 0.0)
+                            .ok_or_else(error)?,
+                    );
+                }
+                ("funds", [amount]) => {
+                    scenario.funds = Some(amount.parse().map_err(|_| error())?);
+                }
+                ("events", ["off"]) => scenario.events.random = None,
+                ("events", ["random", seed, interval]) => {
+                    scenario.events.random = Some((
+                        seed.parse().map_err(|_| error())?,
+                        parse_seconds(interval)
+                            .filter(|interval| *interval > 0.0)
+                            .ok_or_else(error)?,
+                    ));
+                }
+                ("event", [kind, start, duration]) => {
+                    scenario.events.scripted.push(ScheduledEvent {
+                        kind: EventKind::from_id(kind).ok_or_else(error)?,
+                        start: parse_seconds(start).ok_or_else(error)?,
+                        duration: parse_seconds(duration).ok_or_else(error)?,
+                    });
+                }
+                ("objective", ["keep", count, satisfaction, seconds]) => {
+                    scenario.objectives.push(Objective::Keep {
+                        count: count.parse().map_err(|_| error())?,
+                        satisfaction: satisfaction.parse().map_err(|_| error())?,
+                        seconds: seconds.parse().map_err(|_| error())?,
+                    });
+                }
+                ("objective", ["population", count]) => {
+                    scenario
+                        .objectives
+                        .push(Objective::Population(count.parse().map_err(|_| error())?));
+                }
+                ("objective", ["score", value, seconds]) => {
+                    scenario.objectives.push(Objective::Score {
+                        value: value.parse().map_err(|_| error())?,
+                        seconds: seconds.parse().map_err(|_| error())?,
+                    });
+                }
+                _ => return Err(error()),
+            }
+        }
+
+        if scenario.name.is_empty() {
+            return Err("Scenario without a name".to_string());
+        }
+        Ok(scenario)
+    }
+}
+
+fn parse_tile(x_pos: &str, y_pos: &str) -> Option<IVec2> {
+    Some(IVec2::new(x_pos.parse().ok()?, y_pos.parse().ok()?))
+}
+
+/// A finite, non-negative number of seconds
+fn parse_seconds(text: &str) -> Option<f32> {
+    text.parse()
+        .ok()
+        .filter(|seconds: &f32| seconds.is_finite() && *seconds >= 0.0)
+}
+
+/// Every scenario the level select offers.
+#[derive(Resource, Debug)]
+pub struct Scenarios(pub Vec<Scenario>);
+
+/// The index of the scenario being played, or `None` in sandbox mode.
+#[derive(Resource, Debug, Default)]
+pub struct ActiveScenario(pub Option<usize>);
+
+/// The names of the scenarios the player has completed.
+#[derive(Resource, Debug, Default)]
+pub struct ScenarioProgress {
+    pub completed: HashSet<String>,
+}
+
+/// How far along each objective of the active scenario is.
+#[derive(Resource, Debug, Default)]
+pub struct ObjectiveStates(Vec<ObjectiveState>);
+
+#[derive(Debug, Default, Clone)]
+struct ObjectiveState {
+    /// For how long the objective's condition has held, in seconds
+    held: f32,
+    reached: bool,
+}
+
+#[derive(Component, Debug)]
+struct ObjectivesText;
+
+pub struct ScenarioPlugin;
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        let scenarios = BUNDLED_SCENARIOS
+            .iter()
+            .map(|text| Scenario::parse(text).expect("Invalid bundled scenario"))
+            .collect();
+
+        app.insert_resource(Scenarios(scenarios))
+            .insert_resource(load_progress())
+            .init_resource::<ActiveScenario>()
+            .init_resource::<ObjectiveStates>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (start_scenario, spawn_objectives_text)
+                    .chain()
+                    .after(build_map),
+            )
+            .add_systems(
+                Update,
+                (track_objectives, update_objectives_text)
+                    .chain()
+                    .after(desire_movement)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_objectives_text);
+    }
+}
+
+/// Reset what scenarios may change, then set up the active scenario if any
+#[allow(clippy::too_many_arguments)]
+pub fn start_scenario(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    scenarios: Res<Scenarios>,
+    active: Res<ActiveScenario>,
+    mut cooldowns: ResMut<BuildingCooldowns>,
+    mut unlocked: ResMut<UnlockedBuildings>,
+    mut spawn_timer: ResMut<SpawnTimer>,
+    mut used_ids: ResMut<UsedPersons>,
+    mut objective_states: ResMut<ObjectiveStates>,
+    mut treasury: ResMut<Treasury>,
+) {
+    *cooldowns = BuildingCooldowns::default();
+    *unlocked = UnlockedBuildings::default();
+    *treasury = Treasury::default();
+    spawn_timer.0 = Timer::from_seconds(BASE_SPAWN_DELAY, TimerMode::Repeating);
+    objective_states.0.clear();
+
+    let Some(scenario) = active.0.and_then(|i| scenarios.0.get(i)) else {
+        return;
+    };
+
+    if let Some(b_types) = &scenario.unlocked {
+        unlocked.0 = b_types.iter().copied().collect();
+    }
+    for (b_type, seconds) in &scenario.cooldowns {
+        if let Some(cooldown) = cooldowns.0.get_mut(b_type) {
+            cooldown.timer = Timer::from_seconds(*seconds, TimerMode::Repeating);
+        }
+    }
+    if let Some(seconds) = scenario.spawn_delay {
+        spawn_timer.0 = Timer::from_seconds(seconds, TimerMode::Repeating);
+    }
+    if let Some(amount) = scenario.funds {
+        treasury.balance = amount;
+    }
+    for (b_type, tile_pos) in &scenario.buildings {
+        // Building positions are their bottom left tile, like in the map files
+        let position = to_world_pos(*tile_pos) + get_size_from_type(b_type) / 2.0;
+        spawn_building(*b_type, position, &mut commands, &asset_handles);
+    }
+    for (count, tile_pos) in &scenario.people {
+        for _ in 0..*count {
+            spawn_new_person(
+                to_world_pos(*tile_pos),
+                &mut commands,
+                &asset_handles,
+                &mut used_ids,
+            );
+        }
+    }
+    objective_states.0 = vec![ObjectiveState::default(); scenario.objectives.len()];
+}
+
+/// Check every objective of the active scenario, and record
+/// the scenario as completed once they are all reached
+#[allow(clippy::too_many_arguments)]
+fn track_objectives(
+    scenarios: Res<Scenarios>,
+    active: Res<ActiveScenario>,
+    mut objective_states: ResMut<ObjectiveStates>,
+    mut progress: ResMut<ScenarioProgress>,
+    persons_query: Query<&Person>,
+    used_ids: Res<UsedPersons>,
+    score: Res<Score>,
+    time: Res<Time>,
+) {
+    let Some(scenario) = active.0.and_then(|i| scenarios.0.get(i)) else {
+        return;
+    };
+    for (objective, state) in scenario.objectives.iter().zip(&mut objective_states.0) {
+        if state.reached {
+            continue;
+        }
+        if objective.holds(&persons_query, used_ids.list.len(), score.0) {
+            state.held += time.delta_seconds();
+            state.reached = state.held >= objective.duration();
+        } else {
+            state.held = 0.0;
+        }
+    }
+
+    if objective_states.0.iter().all(|state| state.reached)
+        && progress.completed.insert(scenario.name.clone())
+    {
+        save_progress(&progress);
+    }
+}
+
+fn spawn_objectives_text(mut commands: Commands, active: Res<ActiveScenario>) {
+    if active.0.is_none() {
+        return;
+    }
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: TEXT_SIZE - 8.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::VMax(1.0),
+            top: Val::VMin(12.0),
+            ..default()
+        }),
+        ObjectivesText,
+    ));
+}
+
+fn update_objectives_text(
+    scenarios: Res<Scenarios>,
+    active: Res<ActiveScenario>,
+    objective_states: Res<ObjectiveStates>,
+    mut text_query: Query<&mut Text, With<ObjectivesText>>,
+) {
+    let Some(scenario) = active.0.and_then(|i| scenarios.0.get(i)) else {
+        return;
+    };
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let mut value = scenario.name.clone();
+    for (objective, state) in scenario.objectives.iter().zip(&objective_states.0) {
+        let status = if state.reached {
+            "[x]".to_string()
+        } else if objective.duration() > 0.0 {
+            format!("[{:.0}/{:.0}s]", state.held, objective.duration())
+        } else {
+            "[ ]".to_string()
+        };
+        value.push_str(&format!("\n{} {}", status, objective.description()));
+    }
+    if objective_states.0.iter().all(|state| state.reached) {
+        value.push_str("\nScenario complete!");
+    }
+    text.sections[0].value = value;
+}
+
+fn cleanup_objectives_text(
+    mut commands: Commands,
+    text_query: Query<Entity, With<ObjectivesText>>,
+) {
+    for entity in &text_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_progress() -> ScenarioProgress {
+    let completed = std::fs::read_to_string(PROGRESS_FILE)
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    ScenarioProgress { completed }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_progress(progress: &ScenarioProgress) {
+    let names: Vec<&str> = progress.completed.iter().map(String::as_str).collect();
+    if let Err(err) = std::fs::write(PROGRESS_FILE, names.join("\n")) {
+        warn!("Could not save scenario progress: {}", err);
+    }
+}
+
+// The web build has nowhere to keep progress, so it only lasts for the session
+
+#[cfg(target_arch = "wasm32")]
+fn load_progress() -> ScenarioProgress {
+    ScenarioProgress::default()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_progress(_progress: &ScenarioProgress) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_reject_invalid_times() {
+        for line in [
+            "events random 1 0",
+            "events random 1 -5",
+            "events random 1 inf",
+            "events random 1 NaN",
+            "event fire -1 10",
+            "event fire 10 -1",
+        ] {
+            assert!(
+                Scenario::parse(&format!("name Test\n{line}")).is_err(),
+                "{line}"
+            );
+        }
+        assert!(Scenario::parse("name Test\nevents random 1 30\nevent fire 0 10").is_ok());
+    }
+
+    #[test]
+    fn timers_reject_invalid_durations() {
+        for line in [
+            "cooldown house 0",
+            "cooldown house -5",
+            "cooldown house inf",
+            "cooldown house NaN",
+            "spawn_delay 0",
+            "spawn_delay -1",
+            "spawn_delay inf",
+        ] {
+            assert!(
+                Scenario::parse(&format!("name Test\n{line}")).is_err(),
+                "{line}"
+            );
+        }
+        assert!(Scenario::parse("name Test\ncooldown house 5\nspawn_delay 2.5").is_ok());
+    }
+}
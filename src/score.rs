@@ -2,7 +2,7 @@ use bevy::prelude::*;
 
 use crate::{
     debug::TEXT_SIZE,
-    person::{Person, UsedPersons},
+    person::{update_liked_disliked, Person, UsedPersons},
     states::GameState,
 };
 
@@ -20,7 +20,11 @@ impl Plugin for ScorePlugin {
             .add_systems(OnEnter(GameState::Playing), spawn_score_display)
             .add_systems(
                 PostUpdate,
-                (update_score, update_score_display).run_if(in_state(GameState::Playing)),
+                (
+                    update_score.after(update_liked_disliked),
+                    update_score_display,
+                )
+                    .run_if(in_state(GameState::Playing)),
             )
             .add_systems(OnExit(GameState::Playing), cleanup_score_text);
     }
@@ -53,13 +57,16 @@ fn spawn_score_display(mut commands: Commands) {
     ));
 }
 
-fn update_score(
+pub fn update_score(
     mut score: ResMut<Score>,
     persons_query: Query<&Person>,
     used_ids: Res<UsedPersons>,
 ) {
     // Score is the average satisfaction (for now)
     score.0 = 0.0;
+    if used_ids.list.is_empty() {
+        return;
+    }
     for person in &persons_query {
         score.0 += person.satisfaction;
     }
@@ -75,3 +82,40 @@ fn cleanup_score_text(mut commands: Commands, text_query: Query<Entity, With<Sco
         commands.entity(entity).despawn_recursive();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_app;
+
+    fn score_app(satisfactions: &[f32]) -> App {
+        let mut app = test_app();
+        app.insert_resource(Score(50.0))
+            .insert_resource(UsedPersons {
+                list: (0..satisfactions.len() as i32).collect(),
+            })
+            .add_systems(Update, update_score);
+        for (id, satisfaction) in satisfactions.iter().enumerate() {
+            app.world.spawn(Person {
+                id: id as i32,
+                satisfaction: *satisfaction,
+                ..default()
+            });
+        }
+        app
+    }
+
+    #[test]
+    fn score_is_the_average_satisfaction() {
+        let mut app = score_app(&[40.0, 80.0, 90.0]);
+        app.update();
+        assert_eq!(app.world.resource::<Score>().0, 70.0);
+    }
+
+    #[test]
+    fn empty_city_scores_zero() {
+        let mut app = score_app(&[]);
+        app.update();
+        assert_eq!(app.world.resource::<Score>().0, 0.0);
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    blueprint::{place_ghost, type_blueprint_name},
+    building::{footprint, BuildingType},
+    camera::CursorPosition,
+    drag::{dragging_system, Draggable},
+    economy::{Paid, Treasury},
+    history::ActionDone,
+    minimap::click_minimap,
+    notification::{Category, Notification},
+    person::Person,
+    replay::replay_inputs,
+    states::GameState,
+    tools::{demolish, demolition_message, right_click_demolish},
+};
+
+/// Marker for the buildings in the player's selection.
+#[derive(Component, Debug)]
+pub struct Selected;
+
+/// Where the selection box was started, while Shift-dragging.
+#[derive(Resource, Debug, Default)]
+struct BoxSelect(Option<Vec2>);
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BoxSelect>()
+            .add_systems(OnEnter(GameState::Playing), reset_selection)
+            .add_systems(
+                PreUpdate,
+                start_box_select
+                    .after(InputSystem)
+                    .after(click_minimap)
+                    .after(replay_inputs)
+                    .after(type_blueprint_name)
+                    .before(place_ghost)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (box_select, delete_selection)
+                        .chain()
+                        .after(right_click_demolish),
+                    clear_selection.after(dragging_system),
+                    highlight_selection,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn reset_selection(mut box_select: ResMut<BoxSelect>) {
+    box_select.0 = None;
+}
+
+/// Shift and left click starts a selection box, instead of picking a building up
+fn start_box_select(
+    mut buttons: ResMut<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor_pos: Res<CursorPosition>,
+    mut box_select: ResMut<BoxSelect>,
+) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift && buttons.just_pressed(MouseButton::Left) {
+        box_select.0 = Some(cursor_pos.0);
+        buttons.clear_just_pressed(MouseButton::Left);
+    }
+}
+
+/// Draw the selection box, and select every building
+/// centered in it once the button is released
+fn box_select(
+    mut commands: Commands,
+    mut box_select: ResMut<BoxSelect>,
+    buttons: Res<Input<MouseButton>>,
+    cursor_pos: Res<CursorPosition>,
+    buildings_query: Query<(Entity, &Transform, Has<Selected>), With<BuildingType>>,
+    mut gizmos: Gizmos,
+) {
+    let Some(start) = box_select.0 else {
+        return;
+    };
+    let rect = Rect::from_corners(start, cursor_pos.0);
+
+    if buttons.pressed(MouseButton::Left) {
+        gizmos.rect_2d(rect.center(), 0.0, rect.size(), Color::CYAN);
+        return;
+    }
+    for (entity, transform, selected) in &buildings_query {
+        let inside = rect.contains(transform.translation.truncate());
+        match (inside, selected) {
+            (true, false) => {
+                commands.entity(entity).insert(Selected);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<Selected>();
+            }
+            _ => {}
+        }
+    }
+    box_select.0 = None;
+}
+
+/// A click that doesn't grab the selection drops it
+pub fn clear_selection(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    selected_query: Query<(Entity, &Draggable), With<Selected>>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if selected_query
+        .iter()
+        .any(|(_, draggable)| draggable.being_dragged)
+    {
+        return;
+    }
+    for (entity, _) in &selected_query {
+        commands.entity(entity).remove::<Selected>();
+    }
+}
+
+fn highlight_selection(
+    selected_query: Query<(&BuildingType, &Transform), With<Selected>>,
+    mut gizmos: Gizmos,
+) {
+    for (b_type, transform) in &selected_query {
+        gizmos.rect_2d(
+            transform.translation.truncate(),
+            0.0,
+            footprint(*b_type, transform),
+            Color::CYAN,
+        );
+    }
+}
+
+/// Delete demolishes every selected building
+#[allow(clippy::type_complexity)]
+pub fn delete_selection(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    selected_query: Query<
+        (Entity, &BuildingType, &Draggable, &Transform, Option<&Paid>),
+        (With<Selected>, Without<Person>),
+    >,
+    mut persons_query: Query<&mut Transform, With<Person>>,
+    mut treasury: ResMut<Treasury>,
+    mut notifications: EventWriter<Notification>,
+    mut actions: EventWriter<ActionDone>,
+) {
+    if !keys.just_pressed(KeyCode::Delete) || selected_query.is_empty() {
+        return;
+    }
+    let mut b_types = vec![];
+    let mut refund = 0.0;
+    for (entity, b_type, draggable, transform, paid) in &selected_query {
+        let action = demolish(
+            entity,
+            *b_type,
+            draggable,
+            *transform,
+            paid.copied(),
+            &mut commands,
+            &mut persons_query,
+            &mut treasury,
+        );
+        refund += action.refund();
+        actions.send(ActionDone(action));
+        b_types.push(*b_type);
+    }
+    notifications.send(Notification::new(
+        Category::Building,
+        demolition_message(&b_types, refund),
+    ));
+}
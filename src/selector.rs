@@ -2,9 +2,8 @@ use bevy::prelude::*;
 
 use crate::{
     asset_loader::AssetHandles,
-    camera::CursorPosition,
     debug::TEXT_SIZE,
-    drag::clicked_on,
+    drag::Hovered,
     movement::{
         CREAT_THRESHOLD, ENTERT_THRESHOLD, HEALTH_THRESHOLD, HUNGER_THRESHOLD, SHELTER_THRESHOLD,
         SOCIAL_THRESHOLD, SPORT_THRESHOLD,
@@ -133,6 +132,8 @@ fn spawn_person_info(mut commands: Commands) {
             TextSection::new("", text_style.clone()),
             TextSection::new("\nSatisfaction: ", text_style.clone()),
             TextSection::new("", text_style.clone()),
+            TextSection::new("\nSurroundings: ", text_style.clone()),
+            TextSection::new("", text_style.clone()),
             TextSection::new("\nCurrent Problems: ", text_style),
             TextSection::new("", problem_style),
         ])
@@ -167,80 +168,79 @@ fn update_person_info(
             text.sections[13].value = format!("{:.0}", person.sport);
             text.sections[15].value = format!("{:.0}", person.creativity);
             text.sections[17].value = format!("{:.0}", person.satisfaction);
+            text.sections[19].value = format!("{:+.1}", person.beauty);
 
             if person.shelter < SHELTER_THRESHOLD && !problems.shelter {
-                text.sections[19].value.push_str(SHELTER_SENTENCE);
+                text.sections[21].value.push_str(SHELTER_SENTENCE);
                 problems.shelter = true;
             } else if person.shelter >= SHELTER_THRESHOLD {
-                text.sections[19].value = text.sections[19].value.replace(SHELTER_SENTENCE, "");
+                text.sections[21].value = text.sections[21].value.replace(SHELTER_SENTENCE, "");
                 problems.shelter = false;
             }
 
             if person.hunger < HUNGER_THRESHOLD && !problems.hunger {
-                text.sections[19].value.push_str(HUNGER_SENTENCE);
+                text.sections[21].value.push_str(HUNGER_SENTENCE);
                 problems.hunger = true;
             } else if person.hunger >= HUNGER_THRESHOLD {
-                text.sections[19].value = text.sections[19].value.replace(HUNGER_SENTENCE, "");
+                text.sections[21].value = text.sections[21].value.replace(HUNGER_SENTENCE, "");
                 problems.hunger = false;
             }
 
             if person.social < SOCIAL_THRESHOLD && !problems.social {
-                text.sections[19].value.push_str(SOCIAL_SENTENCE);
+                text.sections[21].value.push_str(SOCIAL_SENTENCE);
                 problems.social = true;
             } else if person.social >= SOCIAL_THRESHOLD {
-                text.sections[19].value = text.sections[19].value.replace(SOCIAL_SENTENCE, "");
+                text.sections[21].value = text.sections[21].value.replace(SOCIAL_SENTENCE, "");
                 problems.social = false;
             }
 
             if person.entertained < ENTERT_THRESHOLD && !problems.entertained {
-                text.sections[19].value.push_str(ENTERT_SENTENCE);
+                text.sections[21].value.push_str(ENTERT_SENTENCE);
                 problems.entertained = true;
             } else if person.entertained >= ENTERT_THRESHOLD {
-                text.sections[19].value = text.sections[19].value.replace(ENTERT_SENTENCE, "");
+                text.sections[21].value = text.sections[21].value.replace(ENTERT_SENTENCE, "");
                 problems.entertained = false;
             }
 
             if person.health < HEALTH_THRESHOLD && !problems.health {
-                text.sections[19].value.push_str(HEALTH_SENTENCE);
+                text.sections[21].value.push_str(HEALTH_SENTENCE);
                 problems.health = true;
             } else if person.health >= HEALTH_THRESHOLD {
-                text.sections[19].value = text.sections[19].value.replace(HEALTH_SENTENCE, "");
+                text.sections[21].value = text.sections[21].value.replace(HEALTH_SENTENCE, "");
                 problems.health = false;
             }
 
             if person.sport < SPORT_THRESHOLD && !problems.sport {
-                text.sections[19].value.push_str(SPORT_SENTENCE);
+                text.sections[21].value.push_str(SPORT_SENTENCE);
                 problems.sport = true;
             } else if person.sport >= SPORT_THRESHOLD {
-                text.sections[19].value = text.sections[19].value.replace(SPORT_SENTENCE, "");
+                text.sections[21].value = text.sections[21].value.replace(SPORT_SENTENCE, "");
                 problems.sport = false;
             }
 
             if person.creativity < CREAT_THRESHOLD && !problems.creativity {
-                text.sections[19].value.push_str(CREAT_SENTENCE);
+                text.sections[21].value.push_str(CREAT_SENTENCE);
                 problems.creativity = true;
             } else if person.creativity >= CREAT_THRESHOLD {
-                text.sections[19].value = text.sections[19].value.replace(CREAT_SENTENCE, "");
+                text.sections[21].value = text.sections[21].value.replace(CREAT_SENTENCE, "");
                 problems.creativity = false;
             }
         }
     }
 }
 
+/// Select the clicked person, unless something is drawn over them
 fn switch_selected(
     mut selector_query: Query<&mut Selector>,
     persons_query: Query<&Person>,
     buttons: Res<Input<MouseButton>>,
     keys: Res<Input<KeyCode>>,
-    cursor_pos: Res<CursorPosition>,
+    hovered: Res<Hovered>,
 ) {
     let mut selector = selector_query.single_mut();
     if buttons.just_pressed(MouseButton::Left) {
-        for person in &persons_query {
-            if clicked_on(&cursor_pos, &person.interact) {
-                selector.selected = person.id;
-                break;
-            }
+        if let Some(person) = hovered.0.and_then(|entity| persons_query.get(entity).ok()) {
+            selector.selected = person.id;
         }
     } else if keys.just_pressed(KeyCode::Tab) {
         // Select the least satisfied person
@@ -0,0 +1,513 @@
+use std::collections::VecDeque;
+
+use bevy::{input::InputSystem, prelude::*, ui::UiSystem, utils::HashMap};
+
+use crate::{
+    building::BuildingType,
+    debug::TEXT_SIZE,
+    minimap::click_minimap,
+    movement::desire_movement,
+    overlay::{lacks_need, need_level},
+    person::Person,
+    score::Score,
+    states::GameState,
+};
+
+/// The needs people have, with the building type satisfying each of them.
+pub const NEEDS: [(BuildingType, &str); 7] = [
+    (BuildingType::House, "Shelter"),
+    (BuildingType::Restaurant, "Hunger"),
+    (BuildingType::Forum, "Social"),
+    (BuildingType::Cinema, "Entertainment"),
+    (BuildingType::Hospital, "Health"),
+    (BuildingType::Pool, "Sport"),
+    (BuildingType::Creative, "Creativity"),
+];
+/// Time between two samples, in seconds.
+const SAMPLE_PERIOD: f32 = 2.0;
+/// The number of samples kept; the oldest ones go first.
+const STATS_CAPACITY: usize = 120;
+/// The number of bars of the satisfaction histogram.
+const HISTOGRAM_BINS: usize = 10;
+const GRAPH_HEIGHT: f32 = 140.0;
+const PANEL_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.8);
+
+/// The state of the city at some point of a game.
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    /// Seconds since the game started
+    pub time: f32,
+    pub score: f32,
+    pub population: usize,
+    /// The average of each need, by the building type satisfying it
+    pub needs: HashMap<BuildingType, f32>,
+    pub buildings: HashMap<BuildingType, usize>,
+}
+
+impl Sample {
+    fn value(&self, series: Series) -> f32 {
+        match series {
+            Series::Score => self.score,
+            Series::Population => self.population as f32,
+            Series::Need(b_type) => self.needs.get(&b_type).copied().unwrap_or_default(),
+            Series::Buildings(b_type) => {
+                self.buildings.get(&b_type).copied().unwrap_or_default() as f32
+            }
+        }
+    }
+}
+
+/// The latest samples of the city, the latest last, reset when a game starts.
+#[derive(Resource, Debug, Default)]
+pub struct Stats {
+    pub samples: VecDeque<Sample>,
+    /// Seconds since the game started
+    elapsed: f32,
+    since_sample: f32,
+}
+
+impl Stats {
+    fn push(&mut self, sample: Sample) {
+        if self.samples.len() >= STATS_CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+/// Take a sample of the city as it is now
+pub fn sample_city(
+    time: f32,
+    score: f32,
+    persons: &[&Person],
+    b_types: impl Iterator<Item = BuildingType>,
+) -> Sample {
+    let mut buildings = HashMap::new();
+    for b_type in b_types {
+        *buildings.entry(b_type).or_insert(0) += 1;
+    }
+    let needs = NEEDS
+        .iter()
+        .map(|(b_type, _)| {
+            let total: f32 = persons
+                .iter()
+                .filter_map(|person| need_level(person, *b_type))
+                .sum();
+            (*b_type, total / persons.len().max(1) as f32)
+        })
+        .collect();
+    Sample {
+        time,
+        score,
+        population: persons.len(),
+        needs,
+        buildings,
+    }
+}
+
+/// What the graph of the dashboard shows.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Series {
+    #[default]
+    Score,
+    Population,
+    /// The average of the need satisfied by this building type
+    Need(BuildingType),
+    /// The number of buildings of this type
+    Buildings(BuildingType),
+}
+
+impl Series {
+    fn label(self) -> &'static str {
+        match self {
+            Series::Score => "Score",
+            Series::Population => "Population",
+            Series::Need(b_type) => NEEDS
+                .iter()
+                .find(|(need, _)| *need == b_type)
+                .map_or("Need", |(_, name)| name),
+            Series::Buildings(b_type) => b_type.name(),
+        }
+    }
+}
+
+/// What the dashboard shows.
+#[derive(Resource, Debug, Default)]
+struct Dashboard {
+    shown: bool,
+    series: Series,
+}
+
+#[derive(Component, Debug)]
+struct DashboardPanel;
+
+#[derive(Component, Debug)]
+struct SeriesTab(Series);
+
+/// The row the bars of the graph go in.
+#[derive(Component, Debug)]
+struct Graph;
+
+#[derive(Component, Debug)]
+struct GraphText;
+
+#[derive(Component, Debug)]
+struct Histogram;
+
+#[derive(Component, Debug)]
+struct BreakdownText;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Stats>()
+            .init_resource::<Dashboard>()
+            .add_systems(OnEnter(GameState::Playing), (reset_stats, spawn_dashboard))
+            .add_systems(
+                PreUpdate,
+                catch_dashboard_clicks
+                    .after(InputSystem)
+                    .after(UiSystem::Focus)
+                    .before(click_minimap)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    record_stats,
+                    toggle_dashboard,
+                    pick_series,
+                    draw_graph,
+                    draw_distribution,
+                )
+                    .chain()
+                    .after(desire_movement)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_dashboard);
+    }
+}
+
+fn reset_stats(mut stats: ResMut<Stats>) {
+    *stats = Stats::default();
+}
+
+fn record_stats(
+    mut stats: ResMut<Stats>,
+    score: Res<Score>,
+    persons_query: Query<&Person>,
+    buildings_query: Query<&BuildingType>,
+    time: Res<Time>,
+) {
+    // Only a new sample counts as a change, for the dashboard to redraw
+    let counters = stats.bypass_change_detection();
+    counters.elapsed += time.delta_seconds();
+    counters.since_sample += time.delta_seconds();
+    if counters.since_sample < SAMPLE_PERIOD {
+        return;
+    }
+    counters.since_sample -= SAMPLE_PERIOD;
+
+    let persons: Vec<&Person> = persons_query.iter().collect();
+    let sample = sample_city(
+        stats.elapsed,
+        score.0,
+        &persons,
+        buildings_query.iter().copied(),
+    );
+    stats.push(sample);
+}
+
+fn spawn_dashboard(mut commands: Commands, dashboard: Res<Dashboard>) {
+    let text_style = TextStyle {
+        font_size: TEXT_SIZE - 10.0,
+        ..default()
+    };
+    let tab_rows: [Vec<Series>; 2] = [
+        [Series::Score, Series::Population]
+            .into_iter()
+            .chain(NEEDS.iter().map(|(b_type, _)| Series::Need(*b_type)))
+            .collect(),
+        BuildingType::ALL.map(Series::Buildings).to_vec(),
+    ];
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: match dashboard.shown {
+                        true => Display::Flex,
+                        false => Display::None,
+                    },
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(20.0),
+                    top: Val::Percent(15.0),
+                    width: Val::Percent(60.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: PANEL_BACKGROUND.into(),
+                ..default()
+            },
+            Interaction::default(),
+            DashboardPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section(
+                "City statistics (I)",
+                TextStyle {
+                    font_size: TEXT_SIZE - 6.0,
+                    color: Color::GOLD,
+                    ..default()
+                },
+            ));
+            for row in tab_rows {
+                panel
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_wrap: FlexWrap::Wrap,
+                            column_gap: Val::Px(8.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|tabs| {
+                        for series in row {
+                            tabs.spawn((ButtonBundle::default(), SeriesTab(series)))
+                                .with_children(|tab| {
+                                    tab.spawn(TextBundle::from_section(
+                                        series.label(),
+                                        text_style.clone(),
+                                    ));
+                                });
+                        }
+                    });
+            }
+            panel.spawn((TextBundle::from_section("", text_style.clone()), GraphText));
+            panel.spawn((
+                NodeBundle {
+                    style: Style {
+                        height: Val::Px(GRAPH_HEIGHT),
+                        align_items: AlignItems::FlexEnd,
+                        column_gap: Val::Px(1.0),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.05).into(),
+                    ..default()
+                },
+                Graph,
+            ));
+            panel
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(24.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|column| {
+                        column.spawn(TextBundle::from_section(
+                            "Satisfaction of people",
+                            text_style.clone(),
+                        ));
+                        column.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(200.0),
+                                    height: Val::Px(GRAPH_HEIGHT / 2.0),
+                                    align_items: AlignItems::FlexEnd,
+                                    column_gap: Val::Px(2.0),
+                                    ..default()
+                                },
+                                background_color: Color::rgba(1.0, 1.0, 1.0, 0.05).into(),
+                                ..default()
+                            },
+                            Histogram,
+                        ));
+                    });
+                    row.spawn((TextBundle::from_section("", text_style), BreakdownText));
+                });
+        });
+}
+
+/// I shows or hides the dashboard
+fn toggle_dashboard(
+    keys: Res<Input<KeyCode>>,
+    mut dashboard: ResMut<Dashboard>,
+    mut panel_query: Query<&mut Style, With<DashboardPanel>>,
+) {
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        || !keys.just_pressed(KeyCode::I)
+    {
+        return;
+    }
+    dashboard.shown = !dashboard.shown;
+    for mut style in &mut panel_query {
+        style.display = match dashboard.shown {
+            true => Display::Flex,
+            false => Display::None,
+        };
+    }
+}
+
+/// Keep clicks on the dashboard from reaching the city below
+#[allow(clippy::type_complexity)]
+fn catch_dashboard_clicks(
+    mut buttons: ResMut<Input<MouseButton>>,
+    dashboard: Res<Dashboard>,
+    ui_query: Query<&Interaction, Or<(With<DashboardPanel>, With<SeriesTab>)>>,
+) {
+    let on_dashboard = ui_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if dashboard.shown && on_dashboard {
+        buttons.clear_just_pressed(MouseButton::Left);
+    }
+}
+
+/// Graph the clicked series, and highlight its tab
+fn pick_series(
+    mut dashboard: ResMut<Dashboard>,
+    mut tabs_query: Query<(Ref<Interaction>, &SeriesTab, &mut BackgroundColor)>,
+) {
+    for (interaction, tab, _) in &tabs_query {
+        if interaction.is_changed() && *interaction == Interaction::Pressed {
+            dashboard.series = tab.0;
+        }
+    }
+    for (_, tab, mut background) in &mut tabs_query {
+        background.0 = match tab.0 == dashboard.series {
+            true => Color::DARK_GRAY,
+            false => Color::NONE,
+        };
+    }
+}
+
+/// Draw the chosen series as bars, one per sample, when a sample is taken
+fn draw_graph(
+    mut commands: Commands,
+    stats: Res<Stats>,
+    dashboard: Res<Dashboard>,
+    graph_query: Query<Entity, With<Graph>>,
+    mut text_query: Query<&mut Text, With<GraphText>>,
+) {
+    if !dashboard.shown || (!stats.is_changed() && !dashboard.is_changed()) {
+        return;
+    }
+    let Ok(graph) = graph_query.get_single() else {
+        return;
+    };
+    let values: Vec<f32> = stats
+        .samples
+        .iter()
+        .map(|sample| sample.value(dashboard.series))
+        .collect();
+    let max = values.iter().copied().fold(0.0, f32::max);
+    // Needs and the score are out of 100, counts grow as they like
+    let scale = match dashboard.series {
+        Series::Score | Series::Need(_) => 100.0,
+        Series::Population | Series::Buildings(_) => max.max(1.0),
+    };
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = format!(
+            "{}: {:.0} now, {:.0} at most over the last {:.0}s",
+            dashboard.series.label(),
+            values.last().copied().unwrap_or_default(),
+            max,
+            stats.samples.back().map_or(0.0, |last| last.time)
+                - stats.samples.front().map_or(0.0, |first| first.time),
+        );
+    }
+
+    commands.entity(graph).despawn_descendants();
+    let bar_width = 100.0 / STATS_CAPACITY as f32;
+    let bars: Vec<Entity> = values
+        .iter()
+        .map(|value| {
+            commands
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(bar_width),
+                        height: Val::Percent(100.0 * value / scale),
+                        ..default()
+                    },
+                    background_color: Color::GOLD.into(),
+                    ..default()
+                })
+                .id()
+        })
+        .collect();
+    commands.entity(graph).push_children(&bars);
+}
+
+/// Draw the satisfaction histogram, and count the people below each need's threshold
+fn draw_distribution(
+    mut commands: Commands,
+    stats: Res<Stats>,
+    dashboard: Res<Dashboard>,
+    persons_query: Query<&Person>,
+    histogram_query: Query<Entity, With<Histogram>>,
+    mut text_query: Query<&mut Text, With<BreakdownText>>,
+) {
+    if !dashboard.shown || (!stats.is_changed() && !dashboard.is_changed()) {
+        return;
+    }
+
+    let mut bins = [0usize; HISTOGRAM_BINS];
+    for person in &persons_query {
+        let bin = (person.satisfaction / 100.0 * HISTOGRAM_BINS as f32) as usize;
+        bins[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+    let highest = bins.iter().copied().max().unwrap_or_default().max(1);
+
+    if let Ok(histogram) = histogram_query.get_single() {
+        commands.entity(histogram).despawn_descendants();
+        let bars: Vec<Entity> = bins
+            .iter()
+            .map(|count| {
+                commands
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_grow: 1.0,
+                            height: Val::Percent(100.0 * *count as f32 / highest as f32),
+                            ..default()
+                        },
+                        background_color: Color::ORANGE.into(),
+                        ..default()
+                    })
+                    .id()
+            })
+            .collect();
+        commands.entity(histogram).push_children(&bars);
+    }
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        let mut breakdown = "Below threshold".to_string();
+        for (b_type, name) in NEEDS {
+            let count = persons_query
+                .iter()
+                .filter(|person| lacks_need(person, b_type))
+                .count();
+            breakdown.push_str(&format!("\n{}: {}", name, count));
+        }
+        text.sections[0].value = breakdown;
+    }
+}
+
+fn cleanup_dashboard(mut commands: Commands, panel_query: Query<Entity, With<DashboardPanel>>) {
+    for entity in &panel_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
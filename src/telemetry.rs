@@ -0,0 +1,415 @@
+use bevy::{prelude::*, render::view::VisibilitySystems};
+
+use crate::{
+    building::BuildingType,
+    notification::{Category, Notification},
+    overlay::need_level,
+    person::Person,
+    score::{update_score, Score},
+    states::GameState,
+    stats::NEEDS,
+};
+
+/// Where exported telemetry goes.
+#[cfg(not(target_arch = "wasm32"))]
+const TELEMETRY_DIR: &str = "telemetry";
+/// Ticks between two snapshots of every person, when they are recorded.
+const SNAPSHOT_PERIOD: u64 = 30;
+
+/// The lowest, average and highest level of a need across people.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Spread {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Spread {
+    fn of(levels: impl Iterator<Item = f32>) -> Self {
+        let (count, total, min, max) = levels.fold(
+            (0, 0.0, f32::INFINITY, f32::NEG_INFINITY),
+            |(count, total, min, max), level| {
+                (count + 1, total + level, min.min(level), max.max(level))
+            },
+        );
+        match count {
+            0 => Spread::default(),
+            _ => Spread {
+                mean: total / count as f32,
+                min,
+                max,
+            },
+        }
+    }
+}
+
+/// The aggregates of the city at one tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickRecord {
+    pub tick: u64,
+    /// Seconds since the game started
+    pub time: f32,
+    pub score: f32,
+    pub population: usize,
+    /// In the order of `NEEDS`
+    pub needs: [Spread; NEEDS.len()],
+    /// In the order of `BuildingType::ALL`
+    pub buildings: [usize; BuildingType::ALL.len()],
+}
+
+impl TickRecord {
+    pub fn measure(
+        tick: u64,
+        time: f32,
+        score: f32,
+        persons: &[&Person],
+        b_types: impl Iterator<Item = BuildingType>,
+    ) -> Self {
+        let mut buildings = [0; BuildingType::ALL.len()];
+        for b_type in b_types {
+            if let Some(index) = BuildingType::ALL.iter().position(|t| *t == b_type) {
+                buildings[index] += 1;
+            }
+        }
+        TickRecord {
+            tick,
+            time,
+            score,
+            population: persons.len(),
+            needs: NEEDS.map(|(b_type, _)| {
+                Spread::of(
+                    persons
+                        .iter()
+                        .filter_map(|person| need_level(person, b_type)),
+                )
+            }),
+            buildings,
+        }
+    }
+}
+
+/// One person at one tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersonRecord {
+    pub tick: u64,
+    pub id: i32,
+    pub position: Vec2,
+    pub satisfaction: f32,
+    pub beauty: f32,
+    /// In the order of `NEEDS`
+    pub needs: [f32; NEEDS.len()],
+}
+
+impl PersonRecord {
+    pub fn measure(tick: u64, person: &Person, position: Vec2) -> Self {
+        PersonRecord {
+            tick,
+            id: person.id,
+            position,
+            satisfaction: person.satisfaction,
+            beauty: person.beauty,
+            needs: NEEDS.map(|(b_type, _)| need_level(person, b_type).unwrap_or_default()),
+        }
+    }
+}
+
+/// What has been recorded of the current game. Insert it with `recording`
+/// set before the game starts to record from the first tick, like a headless run does.
+#[derive(Resource, Debug, Default)]
+pub struct Telemetry {
+    pub recording: bool,
+    /// Also record every person, every `SNAPSHOT_PERIOD` ticks
+    pub snapshots: bool,
+    pub tick: u64,
+    pub elapsed: f32,
+    pub ticks: Vec<TickRecord>,
+    pub persons: Vec<PersonRecord>,
+}
+
+impl Telemetry {
+    /// Forget what was recorded, but keep recording if it was
+    pub fn clear(&mut self) {
+        self.tick = 0;
+        self.elapsed = 0.0;
+        self.ticks.clear();
+        self.persons.clear();
+    }
+
+    /// Record a tick of the city, and its people if snapshots are on
+    pub fn record<'a>(
+        &mut self,
+        delta: f32,
+        score: f32,
+        persons: impl Iterator<Item = (&'a Person, Vec2)>,
+        b_types: impl Iterator<Item = BuildingType>,
+    ) {
+        let tick = self.tick;
+        let persons: Vec<(&Person, Vec2)> = persons.collect();
+        let people: Vec<&Person> = persons.iter().map(|(person, _)| *person).collect();
+        self.ticks.push(TickRecord::measure(
+            tick,
+            self.elapsed,
+            score,
+            &people,
+            b_types,
+        ));
+        if self.snapshots && tick.is_multiple_of(SNAPSHOT_PERIOD) {
+            self.persons.extend(
+                persons
+                    .iter()
+                    .map(|(person, position)| PersonRecord::measure(tick, person, *position)),
+            );
+        }
+        self.tick += 1;
+        self.elapsed += delta;
+    }
+
+    pub fn ticks_csv(&self) -> String {
+        let mut header = ["tick", "time", "score", "population"]
+            .map(String::from)
+            .to_vec();
+        for (_, name) in NEEDS {
+            let need = name.to_lowercase();
+            header.extend(["mean", "min", "max"].map(|stat| format!("{}_{}", need, stat)));
+        }
+        header.extend(BuildingType::ALL.map(|b_type| b_type.id().to_string()));
+
+        let mut csv = header.join(",") + "\n";
+        for record in &self.ticks {
+            let mut row = vec![
+                record.tick.to_string(),
+                format!("{:.3}", record.time),
+                format!("{:.2}", record.score),
+                record.population.to_string(),
+            ];
+            for spread in &record.needs {
+                row.extend([spread.mean, spread.min, spread.max].map(|v| format!("{:.2}", v)));
+            }
+            row.extend(record.buildings.map(|count| count.to_string()));
+            csv += &(row.join(",") + "\n");
+        }
+        csv
+    }
+
+    pub fn ticks_json(&self) -> String {
+        let records: Vec<String> = self
+            .ticks
+            .iter()
+            .map(|record| {
+                let needs: Vec<String> = NEEDS
+                    .iter()
+                    .zip(&record.needs)
+                    .map(|((_, name), spread)| {
+                        format!(
+                            "\"{}\":{{\"mean\":{:.2},\"min\":{:.2},\"max\":{:.2}}}",
+                            name.to_lowercase(),
+                            spread.mean,
+                            spread.min,
+                            spread.max
+                        )
+                    })
+                    .collect();
+                let buildings: Vec<String> = BuildingType::ALL
+                    .iter()
+                    .zip(&record.buildings)
+                    .map(|(b_type, count)| format!("\"{}\":{}", b_type.id(), count))
+                    .collect();
+                format!(
+                    "{{\"tick\":{},\"time\":{:.3},\"score\":{:.2},\"population\":{},\"needs\":{{{}}},\"buildings\":{{{}}}}}",
+                    record.tick,
+                    record.time,
+                    record.score,
+                    record.population,
+                    needs.join(","),
+                    buildings.join(",")
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", records.join(",\n"))
+    }
+
+    pub fn persons_csv(&self) -> String {
+        let mut header = ["tick", "id", "x", "y", "satisfaction", "beauty"]
+            .map(String::from)
+            .to_vec();
+        header.extend(NEEDS.map(|(_, name)| name.to_lowercase()));
+
+        let mut csv = header.join(",") + "\n";
+        for record in &self.persons {
+            let mut row = vec![
+                record.tick.to_string(),
+                record.id.to_string(),
+                format!("{:.1}", record.position.x),
+                format!("{:.1}", record.position.y),
+                format!("{:.2}", record.satisfaction),
+                format!("{:.2}", record.beauty),
+            ];
+            row.extend(record.needs.map(|level| format!("{:.2}", level)));
+            csv += &(row.join(",") + "\n");
+        }
+        csv
+    }
+
+    pub fn persons_json(&self) -> String {
+        let records: Vec<String> = self
+            .persons
+            .iter()
+            .map(|record| {
+                let needs: Vec<String> = NEEDS
+                    .iter()
+                    .zip(&record.needs)
+                    .map(|((_, name), level)| format!("\"{}\":{:.2}", name.to_lowercase(), level))
+                    .collect();
+                format!(
+                    "{{\"tick\":{},\"id\":{},\"x\":{:.1},\"y\":{:.1},\"satisfaction\":{:.2},\"beauty\":{:.2},\"needs\":{{{}}}}}",
+                    record.tick,
+                    record.id,
+                    record.position.x,
+                    record.position.y,
+                    record.satisfaction,
+                    record.beauty,
+                    needs.join(",")
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", records.join(",\n"))
+    }
+}
+
+/// Write what was recorded as CSV and JSON files named after `name`,
+/// returning the path of the first one
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_telemetry(telemetry: &Telemetry, name: &str) -> std::io::Result<String> {
+    let dir = std::path::Path::new(TELEMETRY_DIR);
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(format!("{}.csv", name)), telemetry.ticks_csv())?;
+    std::fs::write(dir.join(format!("{}.json", name)), telemetry.ticks_json())?;
+    if telemetry.snapshots {
+        std::fs::write(
+            dir.join(format!("{}_persons.csv", name)),
+            telemetry.persons_csv(),
+        )?;
+        std::fs::write(
+            dir.join(format!("{}_persons.json", name)),
+            telemetry.persons_json(),
+        )?;
+    }
+    Ok(dir.join(format!("{}.csv", name)).display().to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn export_telemetry(_telemetry: &Telemetry, _name: &str) -> std::io::Result<String> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "no file system on the web",
+    ))
+}
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Telemetry>()
+            .add_systems(OnEnter(GameState::Playing), clear_telemetry)
+            .add_systems(
+                Update,
+                toggle_recording.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                PostUpdate,
+                // Like the simulation before it, ahead of sprites getting their
+                // bounds, which changes the order they are seen in
+                record_telemetry
+                    .after(update_score)
+                    .before(VisibilitySystems::CalculateBounds)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn clear_telemetry(mut telemetry: ResMut<Telemetry>) {
+    telemetry.clear();
+}
+
+/// Record the city at the end of every tick
+pub fn record_telemetry(
+    mut telemetry: ResMut<Telemetry>,
+    score: Res<Score>,
+    persons_query: Query<(&Person, &Transform)>,
+    buildings_query: Query<&BuildingType>,
+    time: Res<Time>,
+) {
+    if !telemetry.recording {
+        return;
+    }
+    telemetry.record(
+        time.delta_seconds(),
+        score.0,
+        persons_query
+            .iter()
+            .map(|(person, transform)| (person, transform.translation.truncate())),
+        buildings_query.iter().copied(),
+    );
+}
+
+/// U starts recording, and exports the recording once pressed again.
+/// Shift+U switches snapshots of every person on or off
+fn toggle_recording(
+    keys: Res<Input<KeyCode>>,
+    mut telemetry: ResMut<Telemetry>,
+    mut notifications: EventWriter<Notification>,
+) {
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        || !keys.just_pressed(KeyCode::U)
+    {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        telemetry.snapshots = !telemetry.snapshots;
+        notifications.send(Notification::new(
+            Category::Progress,
+            match telemetry.snapshots {
+                true => "Telemetry will include every person",
+                false => "Telemetry will only include aggregates",
+            },
+        ));
+        return;
+    }
+
+    if !telemetry.recording {
+        telemetry.clear();
+        telemetry.recording = true;
+        notifications.send(Notification::new(
+            Category::Progress,
+            "Recording telemetry (U to stop and export)",
+        ));
+        return;
+    }
+    telemetry.recording = false;
+    let name = format!("telemetry_{}", unix_seconds());
+    let message = match export_telemetry(&telemetry, &name) {
+        Ok(path) => format!(
+            "{} ticks of telemetry exported to {}",
+            telemetry.ticks.len(),
+            path
+        ),
+        Err(error) => {
+            warn!("Could not export telemetry: {}", error);
+            "Could not export telemetry".to_string()
+        }
+    };
+    notifications.send(Notification::new(Category::Progress, message));
+}
+
+/// Seconds since the Unix epoch, to name exports after
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn unix_seconds() -> u64 {
+    0
+}
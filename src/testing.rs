@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+
+/// How long a frame lasts in tests, in seconds.
+pub const TEST_TICK: f32 = 0.1;
+
+/// An app without window nor rendering, where every frame lasts `TEST_TICK`,
+/// already past its first frame where no time passes
+pub fn test_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            TEST_TICK,
+        )));
+    app.update();
+    app
+}
@@ -0,0 +1,313 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::{
+    blueprint::place_ghost,
+    building::{footprint, BuildingType},
+    debug::TEXT_SIZE,
+    drag::{find_hovered, Draggable, Hovered},
+    economy::{Paid, Treasury},
+    ground::Ground,
+    history::{step_history, Action, ActionDone},
+    minimap::click_minimap,
+    notification::{Category, Notification},
+    person::Person,
+    replay::replay_inputs,
+    states::GameState,
+};
+
+/// The part of its price a demolished building gives back.
+const REFUND_RATE: f32 = 0.5;
+/// How far out of a demolished building its occupants are pushed.
+const PUSH_MARGIN: f32 = 10.0;
+
+/// What a left click on a building does.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub enum Tool {
+    /// Drag buildings around
+    #[default]
+    Move,
+    Bulldozer,
+    /// Turn buildings by a quarter
+    Rotate,
+}
+
+impl Tool {
+    fn label(self) -> &'static str {
+        match self {
+            Tool::Move => "Move",
+            Tool::Bulldozer => "Bulldozer",
+            Tool::Rotate => "Rotate",
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct ToolText;
+
+pub struct ToolsPlugin;
+
+impl Plugin for ToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Tool>()
+            .add_systems(OnEnter(GameState::Playing), (reset_tools, spawn_tool_text))
+            .add_systems(
+                PreUpdate,
+                use_tool
+                    .after(InputSystem)
+                    .after(click_minimap)
+                    .after(find_hovered)
+                    .after(replay_inputs)
+                    .after(place_ghost)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    switch_tool,
+                    right_click_demolish.after(step_history),
+                    update_tool_text,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), cleanup_tool_text);
+    }
+}
+
+fn reset_tools(mut tool: ResMut<Tool>) {
+    *tool = Tool::default();
+}
+
+/// M, B and Q pick the move, bulldozer and rotate tools
+fn switch_tool(mut tool: ResMut<Tool>, keys: Res<Input<KeyCode>>) {
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::M) {
+        *tool = Tool::Move;
+    } else if keys.just_pressed(KeyCode::B) {
+        *tool = Tool::Bulldozer;
+    } else if keys.just_pressed(KeyCode::Q) {
+        *tool = Tool::Rotate;
+    }
+}
+
+/// Apply the bulldozer or rotate tool to the hovered
+/// building, and keep the click from picking it up
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn use_tool(
+    mut commands: Commands,
+    tool: Res<Tool>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    hovered: Res<Hovered>,
+    mut buildings_query: Query<
+        (
+            Entity,
+            &BuildingType,
+            &Draggable,
+            &mut Transform,
+            Option<&Paid>,
+        ),
+        Without<Person>,
+    >,
+    mut persons_query: Query<&mut Transform, With<Person>>,
+    mut treasury: ResMut<Treasury>,
+    mut notifications: EventWriter<Notification>,
+    mut actions: EventWriter<ActionDone>,
+    ground: Res<Ground>,
+) {
+    if *tool == Tool::Move || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(entity) = hovered.0 else {
+        return;
+    };
+    let Ok((_, b_type, draggable, mut transform, paid)) = buildings_query.get_mut(entity) else {
+        return;
+    };
+    buttons.clear_just_pressed(MouseButton::Left);
+
+    match *tool {
+        Tool::Bulldozer => {
+            let action = demolish(
+                entity,
+                *b_type,
+                draggable,
+                *transform,
+                paid.copied(),
+                &mut commands,
+                &mut persons_query,
+                &mut treasury,
+            );
+            notifications.send(Notification::new(
+                Category::Building,
+                demolition_message(&[*b_type], action.refund()),
+            ));
+            actions.send(ActionDone(action));
+        }
+        Tool::Rotate => {
+            let mut rotated = *transform;
+            rotated.rotate_z(FRAC_PI_2);
+            let position = rotated.translation.truncate();
+            if ground.can_build(*b_type, position, footprint(*b_type, &rotated)) {
+                actions.send(ActionDone(Action::Rotated {
+                    entity,
+                    from: transform.rotation,
+                    to: rotated.rotation,
+                }));
+                *transform = rotated;
+            } else {
+                notifications.send(Notification::new(
+                    Category::Building,
+                    format!("No room to turn this {}", b_type.name()),
+                ));
+            }
+        }
+        Tool::Move => {}
+    }
+}
+
+/// Right clicking a building demolishes it, whatever the tool
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn right_click_demolish(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    hovered: Res<Hovered>,
+    buildings_query: Query<
+        (Entity, &BuildingType, &Draggable, &Transform, Option<&Paid>),
+        Without<Person>,
+    >,
+    mut persons_query: Query<&mut Transform, With<Person>>,
+    mut treasury: ResMut<Treasury>,
+    mut notifications: EventWriter<Notification>,
+    mut actions: EventWriter<ActionDone>,
+) {
+    if !buttons.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let Some(entity) = hovered.0 else {
+        return;
+    };
+    if let Ok((_, b_type, draggable, transform, paid)) = buildings_query.get(entity) {
+        let action = demolish(
+            entity,
+            *b_type,
+            draggable,
+            *transform,
+            paid.copied(),
+            &mut commands,
+            &mut persons_query,
+            &mut treasury,
+        );
+        notifications.send(Notification::new(
+            Category::Building,
+            demolition_message(&[*b_type], action.refund()),
+        ));
+        actions.send(ActionDone(action));
+    }
+}
+
+/// Remove a building, refund part of what was paid for it, and push whoever
+/// stands in it out of its footprint. Returns the action, for the history
+#[allow(clippy::too_many_arguments)]
+pub fn demolish(
+    entity: Entity,
+    b_type: BuildingType,
+    draggable: &Draggable,
+    transform: Transform,
+    paid: Option<Paid>,
+    commands: &mut Commands,
+    persons_query: &mut Query<&mut Transform, With<Person>>,
+    treasury: &mut Treasury,
+) -> Action {
+    commands.entity(entity).despawn_recursive();
+    let refund = paid.map_or(0.0, |paid| paid.0 * REFUND_RATE);
+    treasury.balance += refund;
+
+    let bottom_left = draggable.interact.bottom_left;
+    let top_right = draggable.interact.top_right;
+    for mut transform in persons_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let inside = position.cmpgt(bottom_left).all() && position.cmplt(top_right).all();
+        if inside {
+            transform.translation =
+                push_out(position, bottom_left, top_right).extend(transform.translation.z);
+        }
+    }
+
+    Action::Demolished {
+        entity,
+        b_type,
+        transform,
+        paid,
+        refund,
+    }
+}
+
+/// What to tell the player once buildings are demolished
+pub fn demolition_message(b_types: &[BuildingType], refund: f32) -> String {
+    match b_types {
+        [b_type] => format!("{} demolished, ${:.0} refunded", b_type.name(), refund),
+        _ => format!(
+            "{} buildings demolished, ${:.0} refunded",
+            b_types.len(),
+            refund
+        ),
+    }
+}
+
+/// Move a position inside a rectangle to just past its closest edge
+fn push_out(position: Vec2, bottom_left: Vec2, top_right: Vec2) -> Vec2 {
+    let to_left = position.x - bottom_left.x;
+    let to_right = top_right.x - position.x;
+    let to_bottom = position.y - bottom_left.y;
+    let to_top = top_right.y - position.y;
+    let closest = to_left.min(to_right).min(to_bottom).min(to_top);
+
+    if closest == to_left {
+        Vec2::new(bottom_left.x - PUSH_MARGIN, position.y)
+    } else if closest == to_right {
+        Vec2::new(top_right.x + PUSH_MARGIN, position.y)
+    } else if closest == to_bottom {
+        Vec2::new(position.x, bottom_left.y - PUSH_MARGIN)
+    } else {
+        Vec2::new(position.x, top_right.y + PUSH_MARGIN)
+    }
+}
+
+fn spawn_tool_text(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: TEXT_SIZE,
+        ..default()
+    };
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new("Tool: ", text_style.clone()),
+            TextSection::from_style(TextStyle {
+                color: Color::GOLD,
+                ..text_style.clone()
+            }),
+            TextSection::new(" (M/B/Q)", text_style),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            // Above the funds
+            left: Val::VMax(1.0),
+            bottom: Val::VMin(11.0),
+            ..default()
+        }),
+        ToolText,
+    ));
+}
+
+fn update_tool_text(tool: Res<Tool>, mut text_query: Query<&mut Text, With<ToolText>>) {
+    text_query.single_mut().sections[1].value = tool.label().to_string();
+}
+
+fn cleanup_tool_text(mut commands: Commands, text_query: Query<Entity, With<ToolText>>) {
+    for entity in &text_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    building::{BuildingType, UnlockedBuildings},
+    notification::{Category, Notification},
+    person::{Person, UsedPersons},
+    scenario::{start_scenario, ActiveScenario, Objective},
+    score::Score,
+    states::GameState,
+};
+
+/// The milestone unlocking a building type, or `None` if it is available from the start
+pub fn unlock_condition(b_type: BuildingType) -> Option<Objective> {
+    match b_type {
+        BuildingType::House
+        | BuildingType::Restaurant
+        | BuildingType::Tree
+        | BuildingType::Lamp => None,
+        BuildingType::Forum => Some(Objective::Population(5)),
+        BuildingType::Creative => Some(Objective::Population(10)),
+        BuildingType::Hospital => Some(Objective::Population(15)),
+        BuildingType::Cinema => Some(Objective::Population(20)),
+        BuildingType::Pool => Some(Objective::Score {
+            value: 60.0,
+            seconds: 30.0,
+        }),
+    }
+}
+
+/// Buildings get unlocked as the city grows, in sandbox mode.
+#[derive(Resource, Debug, Default)]
+pub struct TechTree {
+    pub active: bool,
+    /// For how long each milestone has held, in seconds
+    held: HashMap<BuildingType, f32>,
+}
+
+pub struct UnlockPlugin;
+
+impl Plugin for UnlockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TechTree>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                start_tech_tree.after(start_scenario),
+            )
+            .add_systems(
+                Update,
+                check_milestones.run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// In sandbox mode, lock every building that has a milestone.
+/// Scenarios choose their own unlocked buildings instead
+fn start_tech_tree(
+    active_scenario: Res<ActiveScenario>,
+    mut tech_tree: ResMut<TechTree>,
+    mut unlocked: ResMut<UnlockedBuildings>,
+) {
+    tech_tree.active = active_scenario.0.is_none();
+    tech_tree.held.clear();
+    if tech_tree.active {
+        unlocked
+            .0
+            .retain(|b_type| unlock_condition(*b_type).is_none());
+    }
+}
+
+/// Unlock buildings whose milestone is reached, and tell the player
+pub fn check_milestones(
+    mut tech_tree: ResMut<TechTree>,
+    mut unlocked: ResMut<UnlockedBuildings>,
+    mut notifications: EventWriter<Notification>,
+    persons_query: Query<&Person>,
+    used_ids: Res<UsedPersons>,
+    score: Res<Score>,
+    time: Res<Time>,
+) {
+    if !tech_tree.active {
+        return;
+    }
+    for b_type in BuildingType::ALL {
+        if unlocked.0.contains(&b_type) {
+            continue;
+        }
+        let Some(condition) = unlock_condition(b_type) else {
+            continue;
+        };
+        let held = tech_tree.held.entry(b_type).or_insert(0.0);
+        if !condition.holds(&persons_query, used_ids.list.len(), score.0) {
+            *held = 0.0;
+            continue;
+        }
+        *held += time.delta_seconds();
+        if *held >= condition.duration() {
+            unlocked.0.insert(b_type);
+            notifications.send(Notification::new(
+                Category::Progress,
+                format!("{} unlocked!", b_type.name()),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashSet;
+
+    use super::*;
+    use crate::testing::test_app;
+
+    #[test]
+    fn empty_city_unlocks_nothing() {
+        let mut app = test_app();
+        app.add_event::<Notification>()
+            .insert_resource(TechTree {
+                active: true,
+                ..default()
+            })
+            .insert_resource(UnlockedBuildings(HashSet::new()))
+            .insert_resource(UsedPersons { list: vec![] })
+            .insert_resource(Score(0.0))
+            .add_systems(Update, check_milestones);
+        for _ in 0..10 {
+            app.update();
+        }
+        assert!(app.world.resource::<UnlockedBuildings>().0.is_empty());
+    }
+}